@@ -1,5 +1,6 @@
 # Use the run script to run these examples, along with the default
 # Configuration!
+#   ./run.ps1 python/1_matplotlib.py -lang python
 x = [1, 2, 3, 4]
 y = [1, 3, 2, 4]
 
@@ -7,5 +8,3 @@ plt.plot(x, y)
 plt.xlabel("X-axis")
 plt.ylabel("Y-axis")
 plt.title("QuickerMD's Awesome Plot")
-
-plt.show()
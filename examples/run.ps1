@@ -1,12 +1,9 @@
 param(
     [Parameter(Mandatory)]
-    [string]$path
-)
-
-$extension = [System.IO.Path]::GetExtension($path).SubString(1)
+    [string]$path,
 
-if ($extension -like "py") {
-    $extension = "python"
-}
+    # The language is looked up from the file extension by default
+    [string]$lang = $path
+)
 
-cat $path | quicker_md run $extension --show-input
+cat $path | quicker_md run $lang --show-input --artifacts artifacts
@@ -0,0 +1,141 @@
+//! AsciiDoc listing blocks, i.e.
+//!
+//! ```asciidoc
+//! [source,py,session=intro]
+//! ----
+//! print("hello")
+//! ----
+//! ```
+
+use super::RawBlock;
+
+/// Returns the attributes of a `[source,py,key=value]` line after `source`
+fn parse_attribute_list(line: &str) -> Option<Vec<&str>> {
+    let list = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+    let mut attributes = list.split(',').map(|attr| attr.trim());
+
+    if attributes.next()? != "source" {
+        return None;
+    }
+
+    Some(attributes.collect())
+}
+
+/// A delimiter is four or more of the same `-` or `.` character
+fn is_delimiter(line: &str) -> bool {
+    let line = line.trim_end();
+
+    line.len() >= 4 && (line.chars().all(|c| c == '-') || line.chars().all(|c| c == '.'))
+}
+
+pub(super) fn find_blocks(lines: &[String]) -> Vec<RawBlock> {
+    let mut blocks = Vec::new();
+    let mut idx = 0;
+
+    while idx < lines.len() {
+        let Some(attributes) = parse_attribute_list(&lines[idx]) else {
+            idx += 1;
+            continue;
+        };
+
+        let start = idx + 1;
+        idx += 1;
+
+        let Some(delimiter) = lines.get(idx).filter(|line| is_delimiter(line)) else {
+            continue;
+        };
+        let delimiter = delimiter.trim_end();
+        idx += 1;
+
+        let content_start = idx;
+        while lines
+            .get(idx)
+            .is_some_and(|line| line.trim_end() != delimiter)
+        {
+            idx += 1;
+        }
+
+        blocks.push(RawBlock {
+            info: attributes.join(" "),
+            lines: lines[content_start..idx.min(lines.len())].to_vec(),
+            start,
+            // An unclosed block runs until the end of the document
+            end: (idx + 1).min(lines.len()),
+        });
+
+        idx += 1;
+    }
+
+    blocks
+}
+
+pub(super) fn render_block(lang: &str, title: Option<&str>, content: &[String]) -> Vec<String> {
+    // The delimiter has to be longer than any delimiter inside of it
+    let longest_inner = content
+        .iter()
+        .filter(|line| line.starts_with('-') && is_delimiter(line))
+        .map(|line| line.trim_end().len())
+        .max()
+        .unwrap_or(3);
+    let delimiter = "-".repeat(longest_inner + 1);
+
+    let mut lines = Vec::with_capacity(content.len() + 3);
+
+    match title {
+        Some(title) => lines.push(format!("[source,{},title=\"{}\"]", lang, title)),
+        None => lines.push(format!("[source,{}]", lang)),
+    }
+    lines.push(delimiter.clone());
+    lines.extend(content.iter().cloned());
+    lines.push(delimiter);
+
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use crate::document::{Document, DocumentFormat, ResultBlock};
+
+    fn parse(contents: &str) -> Document {
+        Document::parse_as(contents, DocumentFormat::AsciiDoc)
+    }
+
+    #[test]
+    fn it_parses_source_blocks_and_attributes() {
+        let document = parse(
+            "= Title\n\n[source, py, session=intro, no_run]\n----\nprint(1)\n------\n----\n[source,sh]\n....\necho\n....\n",
+        );
+        let blocks = document.get_blocks();
+
+        assert_eq!(blocks[0].get_lang(), "py");
+        assert_eq!(blocks[0].get_attributes().get_session(), Some("intro"));
+        assert!(blocks[0].get_attributes().is_no_run());
+        assert_eq!(blocks[0].get_input(), "print(1)\n------");
+        assert_eq!((blocks[0].get_start(), blocks[0].get_end()), (3, 7));
+        assert_eq!(blocks[1].get_input(), "echo");
+    }
+
+    #[test]
+    fn it_needs_a_delimiter_after_the_attribute_list() {
+        let document = parse("[source,py]\nprint(1)\n\n[quote]\n----\nnot code\n----\n");
+
+        assert!(document.get_blocks().is_empty());
+    }
+
+    #[test]
+    fn it_writes_results_as_source_blocks() {
+        let document = parse("[source,sh]\n----\necho ----\n----\n");
+        let results = [Some(vec![ResultBlock::new(
+            "output",
+            Some("Output".to_string()),
+            "----",
+        )])];
+        let written = document.with_results(&results);
+
+        assert_eq!(
+            written,
+            "[source,sh]\n----\necho ----\n----\n\n[source,output,title=\"Output\"]\n-----\n----\n-----\n"
+        );
+        assert_eq!(parse(&written).with_results(&results), written);
+    }
+}
@@ -0,0 +1,193 @@
+//! Fenced code blocks, i.e.
+//!
+//! ````md
+//! ```py session
+//! print("hello")
+//! ```
+//! ````
+
+use super::{RawBlock, ARTIFACT_KIND};
+
+/// An opening fence, i.e. ` ```rust `
+struct Fence {
+    indent: usize,
+    marker: char,
+    len: usize,
+    info: String,
+}
+
+impl Fence {
+    fn parse(line: &str) -> Option<Self> {
+        let trimmed = line.trim_start_matches(' ');
+        let indent = line.len() - trimmed.len();
+
+        if indent > 3 {
+            return None;
+        }
+
+        let marker = trimmed.chars().next()?;
+        if marker != '`' && marker != '~' {
+            return None;
+        }
+
+        let len = trimmed.chars().take_while(|c| *c == marker).count();
+        if len < 3 {
+            return None;
+        }
+
+        let info = trimmed[len..].trim().to_string();
+        if marker == '`' && info.contains('`') {
+            return None;
+        }
+
+        Some(Self {
+            indent,
+            marker,
+            len,
+            info,
+        })
+    }
+
+    fn is_closed_by(&self, line: &str) -> bool {
+        let trimmed = line.trim_start_matches(' ');
+
+        if line.len() - trimmed.len() > 3 {
+            return false;
+        }
+
+        let len = trimmed.chars().take_while(|c| *c == self.marker).count();
+
+        len >= self.len && trimmed[len..].trim().is_empty()
+    }
+
+    /// Removes up to `indent` leading spaces from a content line
+    fn strip_indent<'line>(&self, line: &'line str) -> &'line str {
+        let spaces = line.len() - line.trim_start_matches(' ').len();
+        &line[spaces.min(self.indent)..]
+    }
+}
+
+pub(super) fn find_blocks(lines: &[String]) -> Vec<RawBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(Fence, usize, Vec<String>)> = None;
+
+    for (idx, line) in lines.iter().enumerate() {
+        let line_nr = idx + 1;
+
+        if let Some((fence, start, mut content)) = current.take() {
+            if fence.is_closed_by(line) {
+                blocks.push(RawBlock {
+                    info: fence.info,
+                    lines: content,
+                    start,
+                    end: line_nr,
+                });
+            } else {
+                content.push(fence.strip_indent(line).to_string());
+                current = Some((fence, start, content));
+            }
+        } else if let Some(fence) = Fence::parse(line) {
+            current = Some((fence, line_nr, Vec::new()));
+        }
+    }
+
+    // An unclosed fence runs until the end of the document
+    if let Some((fence, start, content)) = current {
+        blocks.push(RawBlock {
+            info: fence.info,
+            lines: content,
+            start,
+            end: lines.len(),
+        });
+    }
+
+    blocks
+}
+
+pub(super) fn render_block(lang: &str, title: Option<&str>, content: &[String]) -> Vec<String> {
+    // The fence has to be longer than any backtick fence inside of it
+    let longest_inner = content
+        .iter()
+        .map(|line| line.trim_start().chars().take_while(|c| *c == '`').count())
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest_inner.max(2) + 1);
+
+    let mut lines = Vec::with_capacity(content.len() + 2);
+
+    match title {
+        Some(title) => lines.push(format!("{}{} title=\"{}\"", fence, lang, title)),
+        None => lines.push(format!("{}{}", fence, lang)),
+    }
+    lines.extend(content.iter().cloned());
+    lines.push(fence);
+
+    lines
+}
+
+/// Links an image, i.e. `![artifact](README_files/block-1/plot.png)`
+pub(super) fn render_artifact(path: &str) -> String {
+    if path.contains(char::is_whitespace) {
+        format!("![{}](<{}>)", ARTIFACT_KIND, path)
+    } else {
+        format!("![{}]({})", ARTIFACT_KIND, path)
+    }
+}
+
+pub(super) fn is_artifact_line(line: &str) -> bool {
+    let line = line.trim();
+
+    line.strip_prefix("![")
+        .and_then(|rest| rest.strip_prefix(ARTIFACT_KIND))
+        .is_some_and(|rest| rest.starts_with("](") && rest.ends_with(')'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::document::{Document, DocumentFormat, ResultBlock};
+
+    #[test]
+    fn it_uses_a_longer_fence_for_results_holding_fences() {
+        let result = ResultBlock::new("output", None, "```\ninner\n```");
+        let lines = result.to_lines(DocumentFormat::Markdown);
+
+        assert_eq!(lines[0], "````output");
+        assert_eq!(lines[4], "````");
+    }
+
+    #[test]
+    fn it_strips_the_fence_indentation() {
+        let document = Document::parse("  ```js\n  console.log(1)\n    indented\n  ```\n");
+
+        assert_eq!(
+            document.get_blocks()[0].get_lines(),
+            &vec!["console.log(1)".to_string(), "  indented".to_string()]
+        );
+    }
+
+    #[test]
+    fn it_replaces_linked_artifacts_on_rerun() {
+        let document = Document::parse(
+            "```py\nplot()\n```\n\n```output\nplotted\n```\n\n![artifact](doc_files/block-1/old.png)\n![plot](mine.png)\n",
+        );
+        let results = [Some(vec![
+            ResultBlock::new("output", None, "plotted"),
+            ResultBlock::artifact("doc_files/block-1/new plot.png"),
+        ])];
+
+        assert_eq!(document.get_block_at_line(9).unwrap().get_index(), 0);
+        assert!(document.get_block_at_line(10).is_none());
+        assert_eq!(
+            document.with_results(&results),
+            "```py\nplot()\n```\n\n```output\nplotted\n```\n\n![artifact](<doc_files/block-1/new plot.png>)\n![plot](mine.png)\n"
+        );
+    }
+
+    #[test]
+    fn it_ignores_fences_indented_as_code() {
+        let lines: Vec<String> = vec!["    ```py".into(), "    ```".into()];
+
+        assert!(find_blocks(&lines).is_empty());
+    }
+}
@@ -0,0 +1,1208 @@
+mod asciidoc;
+mod markdown;
+mod org;
+mod rst;
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::QuickerError;
+use crate::output::Output;
+use crate::utils;
+
+/// The kinds of blocks that hold the result of the block above them
+pub const RESULT_KINDS: [&str; 4] = ["input", "output", "error", EXPECTED_KIND];
+
+/// The kind of block holding the expected output of the block above it
+pub const EXPECTED_KIND: &str = "expected";
+
+/// The kind of `ResultBlock` linking an image the block above it wrote
+pub const ARTIFACT_KIND: &str = "artifact";
+
+/// The markup language of a document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DocumentFormat {
+    /// Fenced code blocks, i.e. ` ```py `
+    #[default]
+    Markdown,
+    /// `#+begin_src py` source blocks
+    Org,
+    /// `.. code-block:: py` directives
+    ReStructuredText,
+    /// `[source,py]` listing blocks
+    AsciiDoc,
+}
+
+/// A parsed document, holding every code block found in it
+#[derive(Debug)]
+pub struct Document {
+    /// The markup language of the document
+    format: DocumentFormat,
+
+    /// The file the document was read from
+    path: Option<PathBuf>,
+
+    /// The raw lines of the document
+    lines: Vec<String>,
+
+    /// The fenced code blocks, in document order
+    blocks: Vec<CodeBlock>,
+
+    /// Whether the document ended with a newline
+    trailing_newline: bool,
+}
+
+/// A single fenced code block
+#[derive(Debug, Clone, Serialize)]
+pub struct CodeBlock {
+    /// The language of the block, the first word of the info string
+    lang: String,
+
+    /// The full info string, i.e. `rust no_run`
+    ///
+    /// Formats other than markdown are normalized to this form
+    info: String,
+
+    /// The attributes following the language in the info string
+    #[serde(skip)]
+    attributes: BlockAttributes,
+
+    /// The contents of the block
+    lines: Vec<String>,
+
+    /// The line of the opening fence, starting at 1
+    start: usize,
+
+    /// The line of the closing fence, starting at 1
+    end: usize,
+
+    /// The position of the block in `Document::get_blocks`
+    #[serde(skip)]
+    index: usize,
+
+    /// The result blocks directly below this block
+    #[serde(skip)]
+    results: Vec<CodeBlock>,
+
+    /// The last line of the block, its results and the artifacts linked below them
+    #[serde(skip)]
+    region_end: usize,
+}
+
+/// The attributes of a `CodeBlock`, i.e. ` ```c no_run timeout=5 `
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockAttributes {
+    /// Only compile the block, without running it
+    no_run: bool,
+
+    /// The block is expected to exit with a non-zero code
+    should_fail: bool,
+
+    /// The block is not run at all
+    ignore: bool,
+
+    /// Overrides the time limit of the language
+    timeout: Option<Duration>,
+
+    /// The session the block belongs to, empty for the language's session
+    session: Option<String>,
+
+    /// The file the block is tangled into, relative to the document
+    tangle: Option<String>,
+}
+
+/// The blocks of a document that are tangled into the same file
+#[derive(Debug)]
+pub struct TangledFile {
+    /// The path of the file, relative to the document
+    path: String,
+
+    /// The language of the first block tangled into the file
+    lang: String,
+
+    /// The input of every block, in document order
+    lines: Vec<String>,
+}
+
+/// The start of the name of each block's artifacts directory
+const ARTIFACTS_PREFIX: &str = "block-";
+
+/// Hashes the language and input of `block` with FNV-1a, which unlike the
+/// hasher of the standard library is the same across builds
+fn artifacts_hash(block: &CodeBlock) -> u64 {
+    let text = format!("{}\0{}", block.lang, block.get_input());
+
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The blocks run so far in a session, so that later blocks can use
+/// what earlier blocks defined
+#[derive(Debug, Default)]
+pub struct Session {
+    /// The input of every block in the session
+    lines: Vec<String>,
+
+    /// The `stdout` of the last run
+    stdout: String,
+
+    /// The `stderr` of the last run
+    stderr: String,
+}
+
+/// A block holding a result, to be written below a `CodeBlock`
+pub struct ResultBlock {
+    /// The kind of result, one of `RESULT_KINDS`
+    kind: String,
+
+    /// The title shown with the block
+    title: Option<String>,
+
+    lines: Vec<String>,
+}
+
+/// The outcome of checking a `BlockResult` against its `CodeBlock`
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    /// The block ran successfully, and matched its expected output
+    Passed,
+    /// The block was not run, with the reason why
+    Skipped(String),
+    /// The block could not be run, or exited with a non-zero code
+    Failed(String),
+    /// The output of the block did not match its expected output
+    Mismatch { expected: String, actual: String },
+}
+
+/// The result of running a single `CodeBlock`
+#[derive(Serialize)]
+pub struct BlockResult {
+    #[serde(skip)]
+    index: usize,
+    lang: String,
+    start: usize,
+    end: usize,
+    status: BlockStatus,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockStatus {
+    /// The block was run
+    Ran(Output),
+    /// The block was not run, with the reason why
+    Skipped(String),
+    /// There was an error running the block
+    Failed(String),
+}
+
+/// A code block found by a `DocumentFormat`, before it is added to a `Document`
+struct RawBlock {
+    /// The info string, as `lang word key=value`
+    info: String,
+    lines: Vec<String>,
+    start: usize,
+    end: usize,
+}
+
+/// Removes the indentation shared by every non-blank line
+fn dedent(lines: Vec<String>) -> Vec<String> {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .into_iter()
+        .map(|line| line.get(indent..).unwrap_or_default().to_string())
+        .collect()
+}
+
+impl DocumentFormat {
+    /// Guesses the format of a document from its file extension,
+    /// defaulting to markdown
+    ///
+    /// ```
+    /// use quickermd::document::DocumentFormat;
+    /// use std::path::Path;
+    ///
+    /// assert_eq!(DocumentFormat::from_path(Path::new("notes.org")), DocumentFormat::Org);
+    /// assert_eq!(DocumentFormat::from_path(Path::new("README")), DocumentFormat::Markdown);
+    /// ```
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match extension.as_str() {
+            "org" => DocumentFormat::Org,
+            "rst" | "rest" => DocumentFormat::ReStructuredText,
+            "adoc" | "asciidoc" | "asc" => DocumentFormat::AsciiDoc,
+            _ => DocumentFormat::Markdown,
+        }
+    }
+
+    fn find_blocks(&self, lines: &[String]) -> Vec<RawBlock> {
+        match self {
+            DocumentFormat::Markdown => markdown::find_blocks(lines),
+            DocumentFormat::Org => org::find_blocks(lines),
+            DocumentFormat::ReStructuredText => rst::find_blocks(lines),
+            DocumentFormat::AsciiDoc => asciidoc::find_blocks(lines),
+        }
+    }
+
+    /// Artifacts are only linked in markdown documents
+    fn render_artifact(&self, path: &str) -> Vec<String> {
+        match self {
+            DocumentFormat::Markdown => vec![markdown::render_artifact(path)],
+            _ => Vec::new(),
+        }
+    }
+
+    fn is_artifact_line(&self, line: &str) -> bool {
+        match self {
+            DocumentFormat::Markdown => markdown::is_artifact_line(line),
+            _ => false,
+        }
+    }
+
+    fn render_block(&self, lang: &str, title: Option<&str>, lines: &[String]) -> Vec<String> {
+        match self {
+            DocumentFormat::Markdown => markdown::render_block(lang, title, lines),
+            DocumentFormat::Org => org::render_block(lang, title, lines),
+            DocumentFormat::ReStructuredText => rst::render_block(lang, title, lines),
+            DocumentFormat::AsciiDoc => asciidoc::render_block(lang, title, lines),
+        }
+    }
+}
+
+impl Document {
+    /// Parses a markdown document
+    ///
+    /// ```
+    /// use quickermd::document::Document;
+    ///
+    /// let document = Document::parse("# Title\n```py\nprint('hello')\n```\n");
+    /// let block = &document.get_blocks()[0];
+    ///
+    /// assert_eq!(block.get_lang(), "py");
+    /// assert_eq!(block.get_input(), "print('hello')");
+    /// assert_eq!((block.get_start(), block.get_end()), (2, 4));
+    /// ```
+    pub fn parse(contents: &str) -> Self {
+        Self::parse_as(contents, DocumentFormat::Markdown)
+    }
+
+    /// Parses a document written in `format`
+    ///
+    /// ```
+    /// use quickermd::document::{Document, DocumentFormat};
+    ///
+    /// let document = Document::parse_as(
+    ///     "* Title\n#+begin_src py :session intro\nprint('hello')\n#+end_src\n",
+    ///     DocumentFormat::Org,
+    /// );
+    /// let block = &document.get_blocks()[0];
+    ///
+    /// assert_eq!(block.get_lang(), "py");
+    /// assert_eq!(block.get_attributes().get_session(), Some("intro"));
+    /// ```
+    pub fn parse_as(contents: &str, format: DocumentFormat) -> Self {
+        let lines: Vec<String> = contents.lines().map(|s| s.to_string()).collect();
+        let mut blocks = Vec::new();
+
+        for raw in format.find_blocks(&lines) {
+            let block = CodeBlock::new(&raw.info, raw.lines, raw.start, raw.end);
+            Document::push_block(&mut blocks, &lines, block);
+        }
+
+        for block in blocks.iter_mut() {
+            block.region_end = block.results.last().map_or(block.end, |result| result.end);
+
+            for (idx, line) in lines.iter().enumerate().skip(block.region_end) {
+                if format.is_artifact_line(line) {
+                    block.region_end = idx + 1;
+                } else if !line.trim().is_empty() {
+                    break;
+                }
+            }
+        }
+
+        Self {
+            format,
+            path: None,
+            lines,
+            blocks,
+            trailing_newline: contents.ends_with('\n'),
+        }
+    }
+
+    /// Pushes a block, attaching it to the previous block if it holds its result
+    fn push_block(blocks: &mut Vec<CodeBlock>, lines: &[String], mut block: CodeBlock) {
+        if block.is_result() {
+            if let Some(previous) = blocks.last_mut() {
+                let previous_end = previous
+                    .results
+                    .last()
+                    .map(|result| result.end)
+                    .unwrap_or(previous.end);
+
+                let only_blank_between = lines[previous_end..block.start - 1]
+                    .iter()
+                    .all(|line| line.trim().is_empty());
+
+                if only_blank_between {
+                    previous.results.push(block);
+                    return;
+                }
+            }
+        }
+
+        block.index = blocks.len();
+        blocks.push(block);
+    }
+
+    /// Reads and parses a document from a file, guessing its format
+    /// from the file extension
+    pub fn from_path(path: &Path) -> Result<Self, QuickerError> {
+        let contents = std::fs::read_to_string(path).map_err(|source| QuickerError::ReadFile {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let mut document = Self::parse_as(&contents, DocumentFormat::from_path(path));
+        document.path = Some(path.to_path_buf());
+
+        Ok(document)
+    }
+
+    /// Returns the file the document was read from
+    pub fn get_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the directory the artifacts of the document are copied to,
+    /// `README_files` next to `README.md`
+    pub fn get_artifacts_dir(&self) -> Option<PathBuf> {
+        let path = self.path.as_ref()?;
+        let stem = path.file_stem()?.to_str()?;
+
+        Some(path.with_file_name(format!("{}_files", stem)))
+    }
+
+    /// Returns the directory the artifacts of `block` are copied to, named
+    /// after its language and input so that it does not change when blocks
+    /// are added or removed around it
+    ///
+    /// ```
+    /// use quickermd::document::Document;
+    ///
+    /// let first = Document::parse("```py\nplot()\n```\n");
+    /// let inserted = Document::parse("```py\nsetup()\n```\n```py\nplot()\n```\n");
+    ///
+    /// assert_eq!(
+    ///     first.get_artifacts_name(&first.get_blocks()[0]),
+    ///     inserted.get_artifacts_name(&inserted.get_blocks()[1])
+    /// );
+    /// ```
+    pub fn get_artifacts_name(&self, block: &CodeBlock) -> String {
+        let hash = artifacts_hash(block);
+
+        // Identical blocks are told apart by their order
+        let copies = self.blocks[..block.index]
+            .iter()
+            .filter(|other| artifacts_hash(other) == hash)
+            .count();
+
+        match copies {
+            0 => format!("{}{:016x}", ARTIFACTS_PREFIX, hash),
+            _ => format!("{}{:016x}-{}", ARTIFACTS_PREFIX, hash, copies + 1),
+        }
+    }
+
+    /// Removes the artifact directories of blocks that are no longer in the
+    /// document, leaving anything else in the artifacts directory alone
+    pub fn remove_unused_artifacts(&self) -> std::io::Result<()> {
+        let Some(dir) = self.get_artifacts_dir() else {
+            return Ok(());
+        };
+
+        if !dir.is_dir() {
+            return Ok(());
+        }
+
+        let used: Vec<String> = self
+            .blocks
+            .iter()
+            .map(|block| self.get_artifacts_name(block))
+            .collect();
+
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if name.starts_with(ARTIFACTS_PREFIX)
+                && !used.contains(&name)
+                && entry.file_type()?.is_dir()
+            {
+                std::fs::remove_dir_all(entry.path())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the markup language of the document
+    pub fn get_format(&self) -> DocumentFormat {
+        self.format
+    }
+
+    /// Returns the code blocks of the document, in document order
+    pub fn get_blocks(&self) -> &Vec<CodeBlock> {
+        &self.blocks
+    }
+
+    /// Returns the block that ran to produce `result`
+    pub fn get_block_for(&self, result: &BlockResult) -> &CodeBlock {
+        &self.blocks[result.index]
+    }
+
+    /// Returns the block containing `line`, starting at 1,
+    /// including the result blocks below it
+    ///
+    /// ```
+    /// use quickermd::document::Document;
+    ///
+    /// let document = Document::parse("text\n```py\nprint(1)\n```\n```output\n1\n```\n");
+    ///
+    /// assert!(document.get_block_at_line(1).is_none());
+    /// assert_eq!(document.get_block_at_line(3).unwrap().get_start(), 2);
+    /// assert_eq!(document.get_block_at_line(6).unwrap().get_start(), 2);
+    /// ```
+    pub fn get_block_at_line(&self, line: usize) -> Option<&CodeBlock> {
+        self.blocks
+            .iter()
+            .find(|block| (block.start..=block.region_end).contains(&line))
+    }
+
+    /// Returns the raw lines of the document
+    pub fn get_lines(&self) -> &Vec<String> {
+        &self.lines
+    }
+
+    /// Collects the blocks tagged with `tangle=path` into their files,
+    /// in the order the files first appear
+    ///
+    /// ```
+    /// use quickermd::document::Document;
+    ///
+    /// let document = Document::parse("```c tangle=main.c\nint x;\n```\n```c tangle=main.c\nint y;\n```\n");
+    /// let files = document.tangle();
+    ///
+    /// assert_eq!(files[0].get_path(), "main.c");
+    /// assert_eq!(files[0].get_contents(), "int x;\nint y;\n");
+    /// ```
+    pub fn tangle(&self) -> Vec<TangledFile> {
+        let mut files: Vec<TangledFile> = Vec::new();
+
+        for block in self.blocks.iter() {
+            let Some(path) = block.attributes.get_tangle() else {
+                continue;
+            };
+
+            match files.iter_mut().find(|file| file.path == path) {
+                Some(file) => file.lines.extend(block.lines.iter().cloned()),
+                None => files.push(TangledFile {
+                    path: path.to_string(),
+                    lang: block.lang.clone(),
+                    lines: block.lines.clone(),
+                }),
+            }
+        }
+
+        files
+    }
+
+    /// Returns the document with the result blocks of each code block replaced
+    ///
+    /// `results` is indexed the same as `get_blocks`, and a block
+    /// with `None` is left untouched
+    ///
+    /// ```
+    /// use quickermd::document::{Document, ResultBlock};
+    ///
+    /// let document = Document::parse("```py\nprint(1)\n```\n\n```output\n0\n```\n");
+    /// let results = vec![Some(vec![ResultBlock::new("output", None, "1\n")])];
+    ///
+    /// assert_eq!(
+    ///     document.with_results(&results),
+    ///     "```py\nprint(1)\n```\n\n```output\n1\n```\n"
+    /// );
+    /// ```
+    pub fn with_results(&self, results: &[Option<Vec<ResultBlock>>]) -> String {
+        let mut out: Vec<String> = Vec::with_capacity(self.lines.len());
+        let mut line = 0;
+
+        for (block, block_results) in self.blocks.iter().zip(results) {
+            let Some(block_results) = block_results else {
+                continue;
+            };
+
+            out.extend_from_slice(&self.lines[line..block.end]);
+            line = block.end;
+
+            // Expected output is written by the user, so it is kept as is
+            for expected in block.results.iter().filter(|r| r.lang == EXPECTED_KIND) {
+                out.push(String::new());
+                out.extend_from_slice(&self.lines[expected.start - 1..expected.end]);
+            }
+
+            for result in block_results {
+                let lines = result.to_lines(self.format);
+                if lines.is_empty() {
+                    continue;
+                }
+
+                out.push(String::new());
+                out.extend(lines);
+            }
+
+            line = line.max(block.region_end);
+        }
+
+        out.extend_from_slice(&self.lines[line..]);
+
+        let mut str = out.join("\n");
+        if self.trailing_newline {
+            str.push('\n');
+        }
+
+        str
+    }
+}
+
+impl CodeBlock {
+    fn new(info: &str, lines: Vec<String>, start: usize, end: usize) -> Self {
+        let mut words = info
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty());
+
+        let lang = words
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches(['{', '.'])
+            .trim_end_matches('}')
+            .to_string();
+
+        Self {
+            lang,
+            info: info.to_string(),
+            attributes: BlockAttributes::parse(words),
+            lines,
+            start,
+            end,
+            index: 0,
+            results: Vec::new(),
+            region_end: end,
+        }
+    }
+
+    /// Whether the block holds the result of the block above it
+    pub fn is_result(&self) -> bool {
+        RESULT_KINDS.contains(&self.lang.as_str())
+    }
+
+    /// Returns the result blocks directly below this block
+    pub fn get_results(&self) -> &Vec<CodeBlock> {
+        &self.results
+    }
+
+    /// Returns the expected output of the block, from the `expected`
+    /// block below it, or the `output` block if there is none
+    pub fn get_expected(&self) -> Option<String> {
+        let find = |kind: &str| self.results.iter().find(|result| result.lang == kind);
+
+        find(EXPECTED_KIND)
+            .or_else(|| find("output"))
+            .map(|result| result.get_input())
+    }
+
+    /// Returns the language of the block
+    pub fn get_lang(&self) -> &str {
+        &self.lang
+    }
+
+    /// Returns the full info string of the block
+    pub fn get_info(&self) -> &str {
+        &self.info
+    }
+
+    /// Returns the attributes of the block
+    pub fn get_attributes(&self) -> &BlockAttributes {
+        &self.attributes
+    }
+
+    /// Returns the session the block runs in, where an empty name refers to
+    /// the session of its language
+    ///
+    /// With `sessions`, a block without a session runs in the session of its language
+    pub fn get_session_name(&self, sessions: bool) -> Option<&str> {
+        match self.attributes.get_session() {
+            Some(name) => Some(name),
+            None if sessions => Some(""),
+            None => None,
+        }
+    }
+
+    /// Returns the contents of the block
+    pub fn get_lines(&self) -> &Vec<String> {
+        &self.lines
+    }
+
+    /// Returns the contents of the block as a single string
+    pub fn get_input(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Returns the position of the block in `Document::get_blocks`
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the line of the opening fence
+    pub fn get_start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the line of the closing fence
+    pub fn get_end(&self) -> usize {
+        self.end
+    }
+}
+
+impl BlockAttributes {
+    /// Parses the words of an info string following the language
+    ///
+    /// Unknown attributes are ignored, as they may belong to other tools
+    fn parse<'word>(words: impl Iterator<Item = &'word str>) -> Self {
+        let mut attributes = Self::default();
+
+        for word in words {
+            let word = word.trim_end_matches('}');
+            let (key, value) = match word.split_once('=') {
+                Some((key, value)) => (key, Some(value.trim_matches('"'))),
+                None => (word, None),
+            };
+
+            match key {
+                "no_run" => attributes.no_run = true,
+                "should_fail" => attributes.should_fail = true,
+                "ignore" => attributes.ignore = true,
+                "session" => attributes.session = Some(value.unwrap_or_default().to_string()),
+                "tangle" => attributes.tangle = value.map(|path| path.to_string()),
+                "timeout" => {
+                    attributes.timeout = value
+                        .and_then(|secs| secs.parse::<f64>().ok())
+                        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                }
+                _ => {}
+            }
+        }
+
+        attributes
+    }
+
+    pub fn is_no_run(&self) -> bool {
+        self.no_run
+    }
+
+    pub fn is_should_fail(&self) -> bool {
+        self.should_fail
+    }
+
+    pub fn is_ignored(&self) -> bool {
+        self.ignore
+    }
+
+    pub fn get_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Returns the file the block is tangled into
+    pub fn get_tangle(&self) -> Option<&str> {
+        self.tangle.as_deref()
+    }
+
+    /// Returns the session of the block, where an empty name
+    /// refers to the session of the language
+    pub fn get_session(&self) -> Option<&str> {
+        self.session.as_deref()
+    }
+}
+
+impl TangledFile {
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_lang(&self) -> &str {
+        &self.lang
+    }
+
+    pub fn get_lines(&self) -> &Vec<String> {
+        &self.lines
+    }
+
+    /// Whether the path stays inside the directory of the document,
+    /// so that a document can not write anywhere else
+    ///
+    /// ```
+    /// use quickermd::document::Document;
+    ///
+    /// let document = Document::parse("```c tangle=../main.c\nint x;\n```\n");
+    ///
+    /// assert!(!document.tangle()[0].is_inside_document_dir());
+    /// ```
+    pub fn is_inside_document_dir(&self) -> bool {
+        utils::is_inside_dir(&self.path)
+    }
+
+    /// Returns the contents of the file
+    pub fn get_contents(&self) -> String {
+        let mut contents = self.lines.join("\n");
+        contents.push('\n');
+        contents
+    }
+}
+
+impl Session {
+    /// Returns the input of the session, followed by the input of `block`
+    pub fn get_input(&self, block: &CodeBlock) -> String {
+        self.lines
+            .iter()
+            .chain(block.get_lines())
+            .cloned()
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Adds `block` to the session, returning only the output that is new
+    /// since the previous block
+    ///
+    /// A block that fails is not added, so that it does not break the blocks after it
+    pub fn push(&mut self, block: &CodeBlock, output: &Output) -> Output {
+        let new_output = |previous: &str, current: String| match current.strip_prefix(previous) {
+            Some(new) => new.to_string(),
+            None => current,
+        };
+
+        let stdout = new_output(&self.stdout, output.get_stdout());
+        let stderr = new_output(&self.stderr, output.get_stderr());
+
+        if output.get_exit_code() == 0 {
+            self.lines.extend(block.get_lines().iter().cloned());
+            self.stdout = output.get_stdout();
+            self.stderr = output.get_stderr();
+        }
+
+        let mut new = Output::new(
+            output.get_format(),
+            Some(stdout),
+            Some(stderr),
+            output.get_exit_code(),
+        );
+        new.set_artifacts(output.get_artifacts().clone());
+
+        new
+    }
+}
+
+impl ResultBlock {
+    pub fn new(kind: &str, title: Option<String>, value: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            title,
+            lines: value.lines().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Creates a result linking the image at `path`, relative to the document
+    pub fn artifact(path: &str) -> Self {
+        Self {
+            kind: ARTIFACT_KIND.to_string(),
+            title: None,
+            lines: vec![path.to_string()],
+        }
+    }
+
+    /// Returns the lines of the block, written in `format`
+    pub fn to_lines(&self, format: DocumentFormat) -> Vec<String> {
+        if self.kind == ARTIFACT_KIND {
+            return format.render_artifact(&self.lines.join(""));
+        }
+
+        format.render_block(&self.kind, self.title.as_deref(), &self.lines)
+    }
+}
+
+impl BlockResult {
+    pub fn new(block: &CodeBlock, status: BlockStatus) -> Self {
+        Self {
+            index: block.index,
+            lang: block.get_lang().to_string(),
+            start: block.get_start(),
+            end: block.get_end(),
+            status,
+        }
+    }
+
+    pub fn get_lang(&self) -> &str {
+        &self.lang
+    }
+
+    pub fn get_start(&self) -> usize {
+        self.start
+    }
+
+    pub fn get_end(&self) -> usize {
+        self.end
+    }
+
+    pub fn get_status(&self) -> &BlockStatus {
+        &self.status
+    }
+
+    /// Checks that the block ran successfully, and that its `stdout`
+    /// matches the expected output of `block`, if it has any
+    pub fn check(&self, block: &CodeBlock) -> CheckStatus {
+        let output = match &self.status {
+            BlockStatus::Ran(output) => output,
+            BlockStatus::Skipped(reason) => return CheckStatus::Skipped(reason.clone()),
+            BlockStatus::Failed(e) => return CheckStatus::Failed(e.clone()),
+        };
+
+        if block.attributes.should_fail {
+            if output.get_exit_code() == 0 {
+                return CheckStatus::Failed("Expected the block to fail".to_string());
+            }
+
+            return CheckStatus::Passed;
+        }
+
+        if output.get_exit_code() != 0 {
+            let mut reason = format!("Exited with code {}", output.get_exit_code());
+
+            if output.has_stderr() {
+                reason.push_str(&format!(":\n{}", output.get_stderr()));
+            }
+
+            return CheckStatus::Failed(reason);
+        }
+
+        if let Some(expected) = block.get_expected() {
+            let expected = normalize_output(&expected);
+            let actual = normalize_output(&output.get_stdout());
+
+            if expected != actual {
+                return CheckStatus::Mismatch { expected, actual };
+            }
+        }
+
+        CheckStatus::Passed
+    }
+}
+
+/// Normalizes line endings and trailing newlines, so outputs can be compared
+fn normalize_output(output: &str) -> String {
+    let mut normalized = output
+        .replace("\r\n", "\n")
+        .trim_end_matches('\n')
+        .to_string();
+    normalized.push('\n');
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_finds_every_fenced_block() {
+        let document = Document::parse(
+            r#"
+# Title
+
+```c
+printf("one");
+```
+
+Some text
+
+~~~py
+print('two')
+~~~
+"#,
+        );
+
+        let blocks = document.get_blocks();
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].get_lang(), "c");
+        assert_eq!(blocks[0].get_input(), r#"printf("one");"#);
+        assert_eq!((blocks[0].get_start(), blocks[0].get_end()), (4, 6));
+        assert_eq!(blocks[1].get_lang(), "py");
+        assert_eq!((blocks[1].get_start(), blocks[1].get_end()), (10, 12));
+    }
+
+    #[test]
+    fn it_does_not_close_on_a_shorter_or_different_fence() {
+        let document = Document::parse("````md\n```py\n~~~\n```\n````\n");
+        let blocks = document.get_blocks();
+
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].get_lang(), "md");
+        assert_eq!(blocks[0].get_lines().len(), 3);
+    }
+
+    #[test]
+    fn it_parses_the_lang_from_the_info_string() {
+        let document = Document::parse("```rust,no_run\n```\n```{.python}\n```\n```\n```\n");
+        let blocks = document.get_blocks();
+
+        assert_eq!(blocks[0].get_lang(), "rust");
+        assert_eq!(blocks[0].get_info(), "rust,no_run");
+        assert_eq!(blocks[1].get_lang(), "python");
+        assert_eq!(blocks[2].get_lang(), "");
+    }
+
+    #[test]
+    fn it_attaches_result_blocks_to_the_block_above() {
+        let document = Document::parse(
+            "```py\nprint(1)\n```\n\n```output\n1\n```\n```error\n```\n\ntext\n\n```output\n```\n",
+        );
+        let blocks = document.get_blocks();
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].get_results().len(), 2);
+        assert_eq!(blocks[1].get_lang(), "output");
+    }
+
+    #[test]
+    fn it_replaces_previous_results_when_rerun() {
+        let contents = "# Doc\n\n```py\nprint(1)\n```\n\ntext\n\n```js\nconsole.log(2)\n```\n";
+        let results = vec![
+            Some(vec![
+                ResultBlock::new("output", Some("Output".to_string()), "1\n"),
+                ResultBlock::new("error", Some("Error".to_string()), "oops\n"),
+            ]),
+            None,
+        ];
+
+        let first = Document::parse(contents).with_results(&results);
+        let second = Document::parse(&first).with_results(&results);
+
+        assert_eq!(first, second);
+        assert_eq!(
+            first,
+            "# Doc\n\n```py\nprint(1)\n```\n\n```output title=\"Output\"\n1\n```\n\n```error title=\"Error\"\noops\n```\n\ntext\n\n```js\nconsole.log(2)\n```\n"
+        );
+
+        let cleared = Document::parse(&first).with_results(&[Some(vec![]), None]);
+        assert_eq!(cleared, contents);
+    }
+
+    #[test]
+    fn it_keeps_expected_blocks_when_writing_results() {
+        let contents = "```py\nprint(1)\n```\n\n```expected\n1\n```\n\n```output\n2\n```\n";
+        let results = vec![Some(vec![ResultBlock::new("output", None, "1\n")])];
+
+        assert_eq!(
+            Document::parse(contents).with_results(&results),
+            "```py\nprint(1)\n```\n\n```expected\n1\n```\n\n```output\n1\n```\n"
+        );
+    }
+
+    #[test]
+    fn it_prefers_expected_blocks_over_output_blocks() {
+        let document = Document::parse("```py\n```\n```output\nold\n```\n```expected\nnew\n```\n");
+
+        assert_eq!(document.get_blocks()[0].get_expected().unwrap(), "new");
+    }
+
+    #[test]
+    fn it_checks_output_against_the_expected_block() {
+        let document = Document::parse("```py\n```\n```expected\nhello\n```\n```py\n```\n");
+        let blocks = document.get_blocks();
+
+        let ran = |stdout: &str, code: i32| {
+            BlockStatus::Ran(Output::new(
+                crate::output::OutputType::Raw,
+                Some(stdout.to_string()),
+                None,
+                code,
+            ))
+        };
+
+        let matching = BlockResult::new(&blocks[0], ran("hello\r\n", 0));
+        assert_eq!(matching.check(&blocks[0]), CheckStatus::Passed);
+
+        let mismatch = BlockResult::new(&blocks[0], ran("bye\n", 0));
+        assert_eq!(
+            mismatch.check(&blocks[0]),
+            CheckStatus::Mismatch {
+                expected: "hello\n".to_string(),
+                actual: "bye\n".to_string()
+            }
+        );
+
+        let no_expected = BlockResult::new(&blocks[1], ran("anything", 0));
+        assert_eq!(no_expected.check(&blocks[1]), CheckStatus::Passed);
+
+        let failed = BlockResult::new(&blocks[1], ran("", 1));
+        assert!(matches!(failed.check(&blocks[1]), CheckStatus::Failed(_)));
+    }
+
+    #[test]
+    fn it_parses_block_attributes() {
+        let document = Document::parse(
+            "```rust no_run\n```\n```py,should_fail\n```\n```c timeout=2.5 ignore\n```\n",
+        );
+        let blocks = document.get_blocks();
+
+        assert!(blocks[0].get_attributes().is_no_run());
+        assert!(blocks[1].get_attributes().is_should_fail());
+        assert!(blocks[2].get_attributes().is_ignored());
+        assert_eq!(
+            blocks[2].get_attributes().get_timeout(),
+            Some(Duration::from_millis(2500))
+        );
+        assert_eq!(blocks[2].get_lang(), "c");
+    }
+
+    #[test]
+    fn it_inverts_the_check_for_should_fail() {
+        let document = Document::parse("```py should_fail\n```\n");
+        let block = &document.get_blocks()[0];
+
+        let ran = |code: i32| {
+            BlockResult::new(
+                block,
+                BlockStatus::Ran(Output::new(
+                    crate::output::OutputType::Raw,
+                    None,
+                    None,
+                    code,
+                )),
+            )
+        };
+
+        assert_eq!(ran(1).check(block), CheckStatus::Passed);
+        assert!(matches!(ran(0).check(block), CheckStatus::Failed(_)));
+    }
+
+    #[test]
+    fn it_only_reports_new_output_in_a_session() {
+        let document = Document::parse("```py session\none\n```\n```py session\ntwo\n```\n");
+        let blocks = document.get_blocks();
+        let mut session = Session::default();
+
+        let ran = |stdout: &str, code: i32| {
+            Output::new(
+                crate::output::OutputType::Raw,
+                Some(stdout.to_string()),
+                None,
+                code,
+            )
+        };
+
+        assert_eq!(blocks[0].get_attributes().get_session(), Some(""));
+        assert_eq!(session.get_input(&blocks[0]), "one");
+        assert_eq!(session.push(&blocks[0], &ran("1\n", 0)).get_stdout(), "1\n");
+
+        assert_eq!(session.get_input(&blocks[1]), "one\ntwo");
+        assert_eq!(
+            session.push(&blocks[1], &ran("1\n2\n", 1)).get_stdout(),
+            "2\n"
+        );
+
+        // The failing block was not added to the session
+        assert_eq!(session.get_input(&blocks[1]), "one\ntwo");
+    }
+
+    #[test]
+    fn it_tangles_blocks_into_files_in_document_order() {
+        let document = Document::parse(
+            "```rs tangle=src/main.rs\nfn main() {\n```\n```toml tangle=\"Cargo.toml\"\n[package]\n```\n```rs\nnot tangled\n```\n```rs tangle=src/main.rs\n}\n```\n",
+        );
+        let files = document.tangle();
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].get_path(), "src/main.rs");
+        assert_eq!(files[0].get_lang(), "rs");
+        assert_eq!(files[0].get_contents(), "fn main() {\n}\n");
+        assert_eq!(files[1].get_path(), "Cargo.toml");
+        assert!(files.iter().all(|file| file.is_inside_document_dir()));
+    }
+
+    #[test]
+    fn it_names_artifact_dirs_after_their_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("README.md");
+        std::fs::write(&path, "```py\nplot()\n```\n```py\nplot()\n```\n").unwrap();
+
+        let document = Document::from_path(&path).unwrap();
+        let blocks = document.get_blocks();
+        let first = document.get_artifacts_name(&blocks[0]);
+        let second = document.get_artifacts_name(&blocks[1]);
+
+        assert!(first.starts_with("block-"));
+        assert_eq!(second, format!("{}-2", first));
+
+        let artifacts = document.get_artifacts_dir().unwrap();
+        for name in [first.as_str(), second.as_str(), "block-removed"] {
+            std::fs::create_dir_all(artifacts.join(name)).unwrap();
+        }
+        std::fs::write(artifacts.join("block-notes.txt"), "").unwrap();
+        std::fs::create_dir(artifacts.join("diagrams")).unwrap();
+
+        // Inserting a block keeps the names of the blocks after it
+        std::fs::write(
+            &path,
+            "```py\nsetup()\n```\n```py\nplot()\n```\n```py\nplot()\n```\n",
+        )
+        .unwrap();
+
+        let document = Document::from_path(&path).unwrap();
+        let blocks = document.get_blocks();
+        assert_eq!(document.get_artifacts_name(&blocks[1]), first);
+        assert_eq!(document.get_artifacts_name(&blocks[2]), second);
+
+        document.remove_unused_artifacts().unwrap();
+        assert!(artifacts.join(&first).exists());
+        assert!(artifacts.join(&second).exists());
+        assert!(!artifacts.join("block-removed").exists());
+        assert!(artifacts.join("block-notes.txt").exists());
+        assert!(artifacts.join("diagrams").exists());
+    }
+
+    #[test]
+    fn it_keeps_tangled_files_inside_the_document_dir() {
+        let document = Document::parse(
+            "```c tangle=./src/../main.c\n```\n```c tangle=../main.c\n```\n```c tangle=src/../../main.c\n```\n```c tangle=/etc/main.c\n```\n```c tangle=src/..\n```\n",
+        );
+        let inside: Vec<bool> = document
+            .tangle()
+            .iter()
+            .map(|file| file.is_inside_document_dir())
+            .collect();
+
+        assert_eq!(inside, vec![true, false, false, false, false]);
+    }
+}
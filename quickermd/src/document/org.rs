@@ -0,0 +1,335 @@
+//! Org-mode source blocks and their results, i.e.
+//!
+//! ```org
+//! #+begin_src py :session intro
+//! print("hello")
+//! #+end_src
+//!
+//! #+RESULTS:
+//! : hello
+//! ```
+
+use super::{dedent, RawBlock, RESULT_KINDS};
+
+const BEGIN: &str = "#+begin_src";
+const END: &str = "#+end_src";
+
+const RESULTS: &str = "#+results";
+const BEGIN_EXAMPLE: &str = "#+begin_example";
+const END_EXAMPLE: &str = "#+end_example";
+
+/// The kind of an unnamed `#+RESULTS:`, which is what org-babel writes
+const DEFAULT_RESULT_KIND: &str = "output";
+
+/// Keywords that can be between `#+RESULTS:` and the result itself
+const AFFILIATED_KEYWORDS: [&str; 3] = ["#+caption:", "#+name:", "#+attr_"];
+
+/// Returns the rest of `line` if it starts with `keyword`, ignoring case
+/// and leading whitespace
+fn strip_keyword<'line>(line: &'line str, keyword: &str) -> Option<&'line str> {
+    let trimmed = line.trim_start();
+    let head = trimmed.get(..keyword.len())?;
+
+    if !head.eq_ignore_ascii_case(keyword) {
+        return None;
+    }
+
+    let rest = &trimmed[keyword.len()..];
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    Some(rest)
+}
+
+/// Turns `py :session intro :results output` into `py session=intro results=output`
+fn normalize_header(header: &str) -> String {
+    let mut words = Vec::new();
+    let mut tokens = header.split_whitespace().peekable();
+
+    while let Some(token) = tokens.next() {
+        let Some(key) = token.strip_prefix(':') else {
+            words.push(token.to_string());
+            continue;
+        };
+
+        match tokens.next_if(|next| !next.starts_with(':')) {
+            Some(value) => words.push(format!("{}={}", key, value)),
+            None => words.push(key.to_string()),
+        }
+    }
+
+    words.join(" ")
+}
+
+/// Org escapes lines starting with `*` or `#+` inside of blocks with a comma
+fn unescape(line: String) -> String {
+    let trimmed = line.trim_start();
+
+    match trimmed.strip_prefix(',') {
+        Some(rest) if rest.starts_with('*') || rest.starts_with("#+") || rest.starts_with(',') => {
+            let indent = line.len() - trimmed.len();
+            format!("{}{}", &line[..indent], rest)
+        }
+        _ => line,
+    }
+}
+
+fn escape(line: &str) -> String {
+    let trimmed = line.trim_start();
+
+    if trimmed.starts_with('*') || trimmed.starts_with("#+") || trimmed.starts_with(",*") {
+        format!(",{}", line)
+    } else {
+        line.to_string()
+    }
+}
+
+/// Returns the name of a `#+RESULTS:` or `#+RESULTS[hash]: name` line
+fn results_name(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    let head = trimmed.get(..RESULTS.len())?;
+
+    if !head.eq_ignore_ascii_case(RESULTS) {
+        return None;
+    }
+
+    let rest = &trimmed[RESULTS.len()..];
+    let rest = match rest.strip_prefix('[') {
+        Some(hashed) => &hashed[hashed.find(']')? + 1..],
+        None => rest,
+    };
+
+    Some(rest.strip_prefix(':')?.trim())
+}
+
+/// Returns the text of a fixed-width `: text` line
+fn fixed_width(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix(':')?;
+
+    if rest.is_empty() {
+        Some(rest)
+    } else {
+        rest.strip_prefix(' ')
+    }
+}
+
+fn is_affiliated_keyword(line: &str) -> bool {
+    let trimmed = line.trim_start().to_lowercase();
+
+    AFFILIATED_KEYWORDS
+        .iter()
+        .any(|keyword| trimmed.starts_with(keyword))
+}
+
+/// Reads the result starting with the `#+RESULTS:` line at `idx`, written as
+/// an example block or as fixed-width lines
+fn find_result(lines: &[String], idx: usize, name: &str) -> Option<RawBlock> {
+    let kind = if RESULT_KINDS.contains(&name) {
+        name
+    } else {
+        DEFAULT_RESULT_KIND
+    };
+
+    let mut next = idx + 1;
+    while lines
+        .get(next)
+        .is_some_and(|line| is_affiliated_keyword(line))
+    {
+        next += 1;
+    }
+
+    if lines
+        .get(next)
+        .is_some_and(|line| strip_keyword(line, BEGIN_EXAMPLE).is_some())
+    {
+        let end = next
+            + 1
+            + lines[next + 1..]
+                .iter()
+                .position(|line| strip_keyword(line, END_EXAMPLE).is_some())?;
+
+        return Some(RawBlock {
+            info: kind.to_string(),
+            lines: dedent(lines[next + 1..end].to_vec())
+                .into_iter()
+                .map(unescape)
+                .collect(),
+            start: idx + 1,
+            end: end + 1,
+        });
+    }
+
+    // An empty result is only the `#+RESULTS:` line
+    let content: Vec<String> = lines[next..]
+        .iter()
+        .map_while(|line| fixed_width(line))
+        .map(|line| line.to_string())
+        .collect();
+
+    Some(RawBlock {
+        info: kind.to_string(),
+        start: idx + 1,
+        end: next + content.len(),
+        lines: content,
+    })
+}
+
+pub(super) fn find_blocks(lines: &[String]) -> Vec<RawBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(String, usize, Vec<String>)> = None;
+    let mut idx = 0;
+
+    while let Some(line) = lines.get(idx) {
+        let line_nr = idx + 1;
+
+        if let Some((info, start, mut content)) = current.take() {
+            if strip_keyword(line, END).is_some() {
+                blocks.push(RawBlock {
+                    info,
+                    lines: dedent(content).into_iter().map(unescape).collect(),
+                    start,
+                    end: line_nr,
+                });
+            } else {
+                content.push(line.clone());
+                current = Some((info, start, content));
+            }
+        } else if let Some(header) = strip_keyword(line, BEGIN) {
+            current = Some((normalize_header(header), line_nr, Vec::new()));
+        } else if let Some(result) =
+            results_name(line).and_then(|name| find_result(lines, idx, name))
+        {
+            idx = result.end;
+            blocks.push(result);
+            continue;
+        }
+
+        idx += 1;
+    }
+
+    // Org does not treat an unclosed block as a block
+    blocks
+}
+
+/// Writes a result the way org-babel does, as a `#+RESULTS:` example block,
+/// naming every kind but `output`
+pub(super) fn render_block(kind: &str, title: Option<&str>, content: &[String]) -> Vec<String> {
+    let mut lines = Vec::with_capacity(content.len() + 4);
+
+    if kind == DEFAULT_RESULT_KIND {
+        lines.push("#+RESULTS:".to_string());
+    } else {
+        lines.push(format!("#+RESULTS: {}", kind));
+    }
+
+    if let Some(title) = title {
+        lines.push(format!("#+CAPTION: {}", title));
+    }
+
+    lines.push(BEGIN_EXAMPLE.to_string());
+    lines.extend(content.iter().map(|line| escape(line)));
+    lines.push(END_EXAMPLE.to_string());
+
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use crate::document::{Document, DocumentFormat, ResultBlock};
+
+    fn parse(contents: &str) -> Document {
+        Document::parse_as(contents, DocumentFormat::Org)
+    }
+
+    #[test]
+    fn it_parses_source_blocks_and_header_arguments() {
+        let document = parse(
+            "* Heading\n#+BEGIN_SRC py :session intro :ignore\n  print(1)\n    nested\n#+END_SRC\n",
+        );
+        let block = &document.get_blocks()[0];
+
+        assert_eq!(block.get_lang(), "py");
+        assert_eq!(block.get_attributes().get_session(), Some("intro"));
+        assert!(block.get_attributes().is_ignored());
+        assert_eq!(block.get_input(), "print(1)\n  nested");
+        assert_eq!((block.get_start(), block.get_end()), (2, 5));
+    }
+
+    #[test]
+    fn it_does_not_match_similar_keywords() {
+        let document = parse("#+begin_srcfoo py\n#+end_src\n#+begin_example\n#+end_example\n");
+
+        assert!(document.get_blocks().is_empty());
+    }
+
+    #[test]
+    fn it_round_trips_escaped_lines() {
+        let result = ResultBlock::new(
+            "output",
+            Some("Output".to_string()),
+            "* not a heading\nplain",
+        );
+        let lines = result.to_lines(DocumentFormat::Org);
+
+        assert_eq!(
+            lines,
+            vec![
+                "#+RESULTS:",
+                "#+CAPTION: Output",
+                "#+begin_example",
+                ",* not a heading",
+                "plain",
+                "#+end_example"
+            ]
+        );
+
+        let document = parse(&format!(
+            "#+begin_src py\n#+end_src\n\n{}\n",
+            lines.join("\n")
+        ));
+        let results = document.get_blocks()[0].get_results();
+
+        assert_eq!(results[0].get_lang(), "output");
+        assert_eq!(results[0].get_input(), "* not a heading\nplain");
+    }
+
+    #[test]
+    fn it_writes_results_as_results_blocks() {
+        let document = parse("#+begin_src sh\necho hi\n#+end_src\nText\n");
+        let written = document.with_results(&[Some(vec![
+            ResultBlock::new("output", None, "hi"),
+            ResultBlock::new("error", None, "oops"),
+        ])]);
+
+        assert_eq!(
+            written,
+            "#+begin_src sh\necho hi\n#+end_src\n\n#+RESULTS:\n#+begin_example\nhi\n#+end_example\n\n#+RESULTS: error\n#+begin_example\noops\n#+end_example\nText\n"
+        );
+        assert_eq!(
+            parse(&written).with_results(&[Some(vec![
+                ResultBlock::new("output", None, "hi"),
+                ResultBlock::new("error", None, "oops"),
+            ])]),
+            written
+        );
+    }
+
+    #[test]
+    fn it_reads_the_results_org_babel_writes() {
+        let document = parse(
+            "#+begin_src sh\necho hi\n#+end_src\n\n#+RESULTS[1a2b]:\n: hi\n:\n: there\nText\n",
+        );
+        let block = &document.get_blocks()[0];
+
+        assert_eq!(document.get_blocks().len(), 1);
+        assert_eq!(block.get_results()[0].get_lang(), "output");
+        assert_eq!(block.get_expected().unwrap(), "hi\n\nthere");
+
+        let written = document.with_results(&[Some(vec![ResultBlock::new("output", None, "bye")])]);
+        assert_eq!(
+            written,
+            "#+begin_src sh\necho hi\n#+end_src\n\n#+RESULTS:\n#+begin_example\nbye\n#+end_example\nText\n"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! reStructuredText code directives, i.e.
+//!
+//! ```rst
+//! .. code-block:: py
+//!    :session: intro
+//!
+//!    print("hello")
+//! ```
+
+use super::{dedent, RawBlock};
+
+const DIRECTIVES: [&str; 3] = ["code-block", "sourcecode", "code"];
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Returns the language of a code directive, which may be empty
+fn parse_directive(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix("..")?.trim_start();
+
+    DIRECTIVES
+        .iter()
+        .find_map(|name| rest.strip_prefix(name)?.strip_prefix("::"))
+        .map(|lang| lang.trim())
+}
+
+/// Turns `:session: intro` into `session=intro`, and `:no_run:` into `no_run`
+fn parse_option(line: &str) -> Option<String> {
+    let (key, value) = line.trim().strip_prefix(':')?.split_once(':')?;
+    let value = value.trim();
+
+    if value.is_empty() {
+        Some(key.to_string())
+    } else {
+        Some(format!("{}={}", key, value))
+    }
+}
+
+pub(super) fn find_blocks(lines: &[String]) -> Vec<RawBlock> {
+    let mut blocks = Vec::new();
+    let mut idx = 0;
+
+    while idx < lines.len() {
+        let Some(lang) = parse_directive(&lines[idx]) else {
+            idx += 1;
+            continue;
+        };
+
+        let start = idx + 1;
+        let indent = indent_of(&lines[idx]);
+        let mut words = vec![lang.to_string()];
+        idx += 1;
+
+        // Options directly follow the directive
+        while let Some(option) = lines
+            .get(idx)
+            .filter(|line| indent_of(line) > indent)
+            .and_then(|line| parse_option(line))
+        {
+            words.push(option);
+            idx += 1;
+        }
+
+        // The content is every following line indented past the directive
+        let mut end = idx;
+        let mut content = Vec::new();
+        while let Some(line) = lines.get(idx) {
+            if line.trim().is_empty() {
+                content.push(String::new());
+            } else if indent_of(line) > indent {
+                content.push(line.clone());
+                end = idx + 1;
+            } else {
+                break;
+            }
+            idx += 1;
+        }
+
+        // The blank lines around the content are not part of it
+        content.truncate(
+            content
+                .iter()
+                .rposition(|l| !l.is_empty())
+                .map_or(0, |i| i + 1),
+        );
+        let leading = content.iter().take_while(|l| l.is_empty()).count();
+        content.drain(..leading);
+
+        blocks.push(RawBlock {
+            info: words.join(" "),
+            lines: dedent(content),
+            start,
+            end,
+        });
+
+        idx = end;
+    }
+
+    blocks
+}
+
+pub(super) fn render_block(lang: &str, title: Option<&str>, content: &[String]) -> Vec<String> {
+    let mut lines = Vec::with_capacity(content.len() + 3);
+
+    lines.push(format!(".. code-block:: {}", lang));
+    if let Some(title) = title {
+        lines.push(format!("   :caption: {}", title));
+    }
+    lines.push(String::new());
+    lines.extend(content.iter().map(|line| {
+        if line.is_empty() {
+            String::new()
+        } else {
+            format!("   {}", line)
+        }
+    }));
+
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use crate::document::{Document, DocumentFormat, ResultBlock};
+
+    fn parse(contents: &str) -> Document {
+        Document::parse_as(contents, DocumentFormat::ReStructuredText)
+    }
+
+    #[test]
+    fn it_parses_code_directives_and_options() {
+        let document = parse(
+            "Title\n=====\n\n.. code-block:: py\n   :session: intro\n   :should_fail:\n\n   print(1)\n\n     nested\n\nAfter\n",
+        );
+        let block = &document.get_blocks()[0];
+
+        assert_eq!(block.get_lang(), "py");
+        assert_eq!(block.get_attributes().get_session(), Some("intro"));
+        assert!(block.get_attributes().is_should_fail());
+        assert_eq!(block.get_input(), "print(1)\n\n  nested");
+        assert_eq!((block.get_start(), block.get_end()), (4, 10));
+    }
+
+    #[test]
+    fn it_accepts_every_code_directive() {
+        let document = parse(".. code:: sh\n\n   echo 1\n\n.. sourcecode:: c\n\n   int x;\n\n.. note::\n\n   not code\n");
+        let langs: Vec<&str> = document.get_blocks().iter().map(|b| b.get_lang()).collect();
+
+        assert_eq!(langs, vec!["sh", "c"]);
+    }
+
+    #[test]
+    fn it_writes_results_as_code_blocks() {
+        let document = parse(".. code-block:: sh\n\n   echo hi\n\nText\n");
+        let results = [Some(vec![ResultBlock::new(
+            "output",
+            Some("Output".to_string()),
+            "hi\n\nthere",
+        )])];
+        let written = document.with_results(&results);
+
+        assert_eq!(
+            written,
+            ".. code-block:: sh\n\n   echo hi\n\n.. code-block:: output\n   :caption: Output\n\n   hi\n\n   there\n\nText\n"
+        );
+
+        let reparsed = parse(&written);
+        assert_eq!(reparsed.get_blocks().len(), 1);
+        assert_eq!(
+            reparsed.get_blocks()[0].get_results()[0].get_input(),
+            "hi\n\nthere"
+        );
+        assert_eq!(reparsed.with_results(&results), written);
+    }
+}
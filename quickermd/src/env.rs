@@ -0,0 +1,107 @@
+//! Expands `${VAR}` and `${VAR:-default}` in config values
+
+/// Replaces every `${VAR}` in `value` with the variable `lookup` finds, and
+/// every `${VAR:-default}` with `default` when the variable is unset or empty
+///
+/// `$${` is a literal `${`. The error describes the first variable that
+/// could not be expanded, i.e. "uses `${CC}`, which is not set and has no default"
+pub fn expand(value: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(idx) = rest.find('$') {
+        expanded.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        if let Some(after) = rest.strip_prefix("$${") {
+            expanded.push_str("${");
+            rest = after;
+            continue;
+        }
+
+        let Some(after) = rest.strip_prefix("${") else {
+            expanded.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+
+        let Some(end) = after.find('}') else {
+            return Err(
+                "has a `${` that is never closed, write `$${` for a literal `${`".to_string(),
+            );
+        };
+
+        let variable = &after[..end];
+        let (name, default) = match variable.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (variable, None),
+        };
+
+        if !is_variable_name(name) {
+            return Err(format!(
+                "uses `${{{}}}`, which is not an environment variable, write `$${{` for a literal `${{`",
+                variable
+            ));
+        }
+
+        match (lookup(name), default) {
+            (Some(value), Some(default)) if value.is_empty() => expanded.push_str(default),
+            (Some(value), _) => expanded.push_str(&value),
+            (None, Some(default)) => expanded.push_str(default),
+            (None, None) => {
+                return Err(format!(
+                    "uses `${{{}}}`, which is not set and has no default",
+                    name
+                ))
+            }
+        }
+
+        rest = &after[end + 1..];
+    }
+
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "CC" => Some("clang".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn it_expands_variables_and_defaults() {
+        assert_eq!(expand("${CC}", lookup).unwrap(), "clang");
+        assert_eq!(
+            expand("${CXX:-g++} -o $out", lookup).unwrap(),
+            "g++ -o $out"
+        );
+        assert_eq!(expand("${EMPTY:-gcc}|${EMPTY}", lookup).unwrap(), "gcc|");
+        assert_eq!(expand("`$${name}`", lookup).unwrap(), "`${name}`");
+    }
+
+    #[test]
+    fn it_fails_on_unset_and_invalid_variables() {
+        assert_eq!(
+            expand("${CXX}", lookup).unwrap_err(),
+            "uses `${CXX}`, which is not set and has no default"
+        );
+        assert!(expand("`${a + b}`", lookup)
+            .unwrap_err()
+            .contains("not an environment variable"));
+        assert!(expand("${CC", lookup).unwrap_err().contains("never closed"));
+    }
+}
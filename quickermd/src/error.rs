@@ -0,0 +1,137 @@
+//! The errors returned by `QuickerMD`
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::validation::ConfigProblem;
+
+#[derive(Debug)]
+pub enum QuickerError {
+    /// No language is configured for the name, alias, extension or file name
+    UnknownLanguage(String),
+
+    /// There is no code block at the line, starting at 1
+    NoBlockAtLine(usize),
+
+    /// The config directory of the platform could not be found
+    NoConfigDir,
+
+    /// A config file does not exist
+    ConfigNotFound(PathBuf),
+
+    /// The config files are not valid TOML, or do not match the config
+    ConfigParse {
+        paths: Vec<PathBuf>,
+        source: toml::de::Error,
+    },
+
+    /// The config has settings that would fail or be ignored when running
+    InvalidConfig(Vec<ConfigProblem>),
+
+    /// A file could not be read
+    ReadFile {
+        path: PathBuf,
+        source: io::Error,
+    },
+
+    /// A program of a command could not be started
+    Spawn {
+        program: String,
+        source: io::Error,
+    },
+
+    /// A command did not finish within the time limit
+    Timeout(Duration),
+
+    /// Any other I/O error while running, like a directory of the run that
+    /// could not be created or a file that could not be written
+    Io(io::Error),
+}
+
+impl fmt::Display for QuickerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuickerError::UnknownLanguage(lang) => write!(f, "No config for `{}`", lang),
+            QuickerError::NoBlockAtLine(line) => write!(f, "No code block at line {}", line),
+            QuickerError::NoConfigDir => write!(f, "Could not resolve project directory"),
+            QuickerError::ConfigNotFound(path) => write!(
+                f,
+                "The config '{}' does not exist",
+                path.to_str().unwrap_or_default()
+            ),
+            QuickerError::ConfigParse { paths, source } => {
+                let paths: Vec<&str> = paths
+                    .iter()
+                    .map(|path| path.to_str().unwrap_or_default())
+                    .collect();
+
+                write!(
+                    f,
+                    "There was an error reading the config '{}':\n{}",
+                    paths.join("', '"),
+                    source
+                )
+            }
+            QuickerError::InvalidConfig(problems) => {
+                write!(f, "There are problems with the config:")?;
+                for problem in problems {
+                    write!(f, "\n{}", problem)?;
+                }
+                Ok(())
+            }
+            QuickerError::ReadFile { path, source } => write!(
+                f,
+                "Error reading file '{}':\n{}",
+                path.to_str().unwrap_or_default(),
+                source
+            ),
+            QuickerError::Spawn { program, source } => {
+                write!(f, "Could not run `{}`: {}", program, source)
+            }
+            QuickerError::Timeout(timeout) => {
+                write!(f, "Timed out after {}s", timeout.as_secs_f64())
+            }
+            QuickerError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for QuickerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            QuickerError::ConfigParse { source, .. } => Some(source),
+            QuickerError::ReadFile { source, .. } => Some(source),
+            QuickerError::Spawn { source, .. } => Some(source),
+            QuickerError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for QuickerError {
+    fn from(e: io::Error) -> Self {
+        QuickerError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn it_chains_the_source_of_a_spawn_error() {
+        let error = QuickerError::Spawn {
+            program: "missing".to_string(),
+            source: io::Error::new(io::ErrorKind::NotFound, "not found"),
+        };
+
+        assert_eq!(error.to_string(), "Could not run `missing`: not found");
+        assert_eq!(error.source().unwrap().to_string(), "not found");
+        assert!(QuickerError::Timeout(Duration::from_secs(1))
+            .source()
+            .is_none());
+    }
+}
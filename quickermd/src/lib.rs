@@ -1,10 +1,76 @@
+pub mod document;
+mod env;
+pub mod error;
 pub mod output;
+pub mod presets;
 mod runner;
+pub mod schema;
+pub mod toolchains;
 pub mod user_config;
 mod utils;
+pub mod validation;
 
+pub use crate::error::QuickerError;
+
+use crate::document::{BlockResult, BlockStatus, CodeBlock, Document, Session};
 use crate::runner::QuickMDRunner;
 use crate::user_config::{Config, LanguageConfig, Template};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Options that change how a single run behaves
+#[derive(Default, Debug, Clone)]
+pub struct RunOptions {
+    /// Only run `command`, skipping the run step
+    no_run: bool,
+
+    /// Overrides the time limit of the language
+    timeout: Option<Duration>,
+
+    /// Where to copy the images written to `{{ARTIFACTS}}`
+    artifacts_dir: Option<PathBuf>,
+
+    /// Where to keep persistent workspaces, in place of the cache directory
+    workspace_root: Option<PathBuf>,
+}
+
+impl RunOptions {
+    pub fn new(no_run: bool, timeout: Option<Duration>) -> Self {
+        Self {
+            no_run,
+            timeout,
+            artifacts_dir: None,
+            workspace_root: None,
+        }
+    }
+
+    /// Keeps the images written to `{{ARTIFACTS}}` by copying them to `dir`
+    pub fn set_artifacts_dir(&mut self, dir: Option<PathBuf>) {
+        self.artifacts_dir = dir;
+    }
+
+    pub fn get_artifacts_dir(&self) -> Option<&PathBuf> {
+        self.artifacts_dir.as_ref()
+    }
+
+    /// Keeps the directories of `workspace = "persistent"` languages in `root`
+    pub fn set_workspace_root(&mut self, root: Option<PathBuf>) {
+        self.workspace_root = root;
+    }
+
+    pub fn get_workspace_root(&self) -> Option<&PathBuf> {
+        self.workspace_root.as_ref()
+    }
+
+    pub fn get_no_run(&self) -> bool {
+        self.no_run
+    }
+
+    pub fn get_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+}
 
 /// # Quicker MD
 ///
@@ -19,13 +85,13 @@ impl QuickerMD {
     ///
     /// ```no_run
     /// use quickermd::QuickerMD;
-    /// # fn main() ->  Result<(), String> {
+    /// # fn main() ->  Result<(), quickermd::QuickerError> {
     /// 
     /// let config = QuickerMD::new()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn new() -> Result<Self, String> {
+    pub fn new() -> Result<Self, QuickerError> {
         Ok(Self {
             config: Config::get_config()?,
         })
@@ -36,37 +102,74 @@ impl QuickerMD {
     /// ```rust
     /// use quickermd::QuickerMD;
     /// use std::path::PathBuf;
-    /// # fn main() -> Result<(), String> {
+    /// # fn main() -> Result<(), quickermd::QuickerError> {
     ///
     /// let config = QuickerMD::from_file_path(PathBuf::from("../examples/config.toml"))?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn from_file_path(file: std::path::PathBuf) -> Result<Self, String> {
+    pub fn from_file_path(file: std::path::PathBuf) -> Result<Self, QuickerError> {
         Ok(Self {
             config: Config::get_config_from_path(file)?,
         })
     }
 
+    /// Returns the merged config
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+
     /// Gets the config for a language
     /// ```
     /// use quickermd::QuickerMD;
     /// use std::path::PathBuf;
-    /// # fn main() -> Result<(), String> {
+    /// # fn main() -> Result<(), quickermd::QuickerError> {
     ///
     /// let config = QuickerMD::from_file_path(PathBuf::from("../examples/config.toml"))?;
     /// let c_config = config.get_config_for_lang("c")?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn get_config_for_lang(&self, lang: &str) -> Result<&LanguageConfig, String> {
-        let config = &self.config;
+    pub fn get_config_for_lang(&self, lang: &str) -> Result<&LanguageConfig, QuickerError> {
+        let lang = self.resolve_lang(lang)?;
 
-        if let Some(lang_conf) = config.get_lang_conf(lang) {
+        if let Some(lang_conf) = self.config.get_lang_conf(&lang) {
             return Ok(lang_conf);
         }
 
-        todo!("handle error")
+        Err(QuickerError::UnknownLanguage(lang))
+    }
+
+    /// Returns the name of the language configured for a fence tag, an alias,
+    /// an extension or a file name
+    ///
+    /// ```
+    /// use quickermd::{QuickerError, QuickerMD};
+    /// use std::path::PathBuf;
+    /// # fn main() -> Result<(), QuickerError> {
+    /// let config = QuickerMD::from_file_path(PathBuf::from("../examples/config.toml"))?;
+    ///
+    /// assert_eq!(config.resolve_lang("c")?, "c");
+    /// assert_eq!(config.resolve_lang("javascript")?, "js");
+    /// assert_eq!(config.resolve_lang(".rs")?, "rust");
+    /// assert_eq!(config.resolve_lang("src/main.rs")?, "rust");
+    /// assert!(matches!(
+    ///     config.resolve_lang("notes.txt"),
+    ///     Err(QuickerError::UnknownLanguage(_))
+    /// ));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resolve_lang(&self, name_or_path: &str) -> Result<String, QuickerError> {
+        let extension = Path::new(name_or_path)
+            .extension()
+            .and_then(|extension| extension.to_str());
+
+        self.config
+            .resolve_lang(name_or_path.trim_start_matches('.'))
+            .or_else(|| self.config.resolve_lang(extension?))
+            .map(|lang| lang.to_string())
+            .ok_or_else(|| QuickerError::UnknownLanguage(name_or_path.to_string()))
     }
 
     /// Returns the template for a language
@@ -82,7 +185,11 @@ impl QuickerMD {
     /// Returns a resolved template for a language
     pub fn get_template(&self, lang: &str, input: Vec<String>) -> Option<Template> {
         if let Ok(lang_conf) = self.get_config_for_lang(lang) {
-            return Some(Template::new(lang, input, lang_conf));
+            return Some(Template::new(
+                lang,
+                lang_conf.get_runnable_lines(input),
+                lang_conf,
+            ));
         }
         None
     }
@@ -91,7 +198,7 @@ impl QuickerMD {
     /// ```
     /// use quickermd::QuickerMD;
     /// use std::path::PathBuf;
-    /// # fn main() -> Result<(), String> {
+    /// # fn main() -> Result<(), quickermd::QuickerError> {
     /// let mut config = QuickerMD::from_file_path(PathBuf::from("../examples/config.toml"))?;
     /// let output = config.run("c", r#"printf("Hello, world!\n");"#.to_string())?;
     /// 
@@ -100,15 +207,205 @@ impl QuickerMD {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn run(&mut self, lang: &str, input: String) -> Result<output::Output, String> {
-        let config = self.get_config_for_lang(lang)?;
-        let template = Template::new(lang, input.lines().map(|s| s.to_string()).collect(), config);
+    pub fn run(&mut self, lang: &str, input: String) -> Result<output::Output, QuickerError> {
+        self.run_with_options(lang, input, &RunOptions::default())
+    }
 
-        let mut runner = QuickMDRunner::new(lang, &template, config, &self.config);
+    /// Runs a template, with `options` changing how it is run
+    ///
+    /// ```
+    /// use quickermd::{QuickerError, QuickerMD, RunOptions};
+    /// use std::path::PathBuf;
+    /// use std::time::Duration;
+    /// # fn main() -> Result<(), QuickerError> {
+    /// let mut config = QuickerMD::from_file_path(PathBuf::from("../examples/config.toml"))?;
+    /// let options = RunOptions::new(false, Some(Duration::from_millis(100)));
+    ///
+    /// assert!(matches!(
+    ///     config.run_with_options("js", "while (true) {}".to_string(), &options),
+    ///     Err(QuickerError::Timeout(_))
+    /// ));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run_with_options(
+        &mut self,
+        lang: &str,
+        input: String,
+        options: &RunOptions,
+    ) -> Result<output::Output, QuickerError> {
+        let lang = self.resolve_lang(lang)?;
+        let config = self.get_config_for_lang(&lang)?;
+        let lines = config.get_runnable_lines(input.lines().map(|s| s.to_string()).collect());
+        let template = Template::new(&lang, lines, config);
+
+        let mut runner = QuickMDRunner::new(&lang, &template, config);
+        runner.set_no_run(options.get_no_run());
+        runner.set_artifacts_dir(options.get_artifacts_dir().cloned());
+        runner.set_workspace_root(options.get_workspace_root().cloned());
+
+        if let Some(timeout) = options.get_timeout() {
+            runner.set_timeout(Some(timeout));
+        }
+
+        runner.start()
+    }
 
-        runner
-            .start()
-            .map_err(|e| format!("There was an error running!:\n{}", e.to_string()))
+    /// Runs a single code block of a document, honoring its attributes
+    pub fn run_block(&mut self, block: &CodeBlock) -> Result<output::Output, QuickerError> {
+        self.run_block_with_input(block, block.get_input(), None)
+    }
+
+    /// Runs `block` with `input`, copying its images into `artifacts_dir`
+    fn run_block_with_input(
+        &mut self,
+        block: &CodeBlock,
+        input: String,
+        artifacts_dir: Option<PathBuf>,
+    ) -> Result<output::Output, QuickerError> {
+        let attributes = block.get_attributes();
+        let mut options = RunOptions::new(attributes.is_no_run(), attributes.get_timeout());
+        options.set_artifacts_dir(artifacts_dir);
+
+        self.run_with_options(block.get_lang(), input, &options)
+    }
+
+    /// Runs every code block of a document that has a config for its language
+    ///
+    /// Blocks tagged with `session=name` build on the blocks before them in the
+    /// same session. With `sessions`, every block of a language shares a session
+    /// by default.
+    ///
+    /// Images written to `{{ARTIFACTS}}` are copied to the artifacts directory
+    /// of the document, when it was read from a file.
+    ///
+    /// ```
+    /// use quickermd::QuickerMD;
+    /// use quickermd::document::{BlockStatus, Document};
+    /// use std::path::PathBuf;
+    /// # fn main() -> Result<(), quickermd::QuickerError> {
+    /// let mut config = QuickerMD::from_file_path(PathBuf::from("../examples/config.toml"))?;
+    /// let document = Document::parse("```py\nprint('hi')\n```\n\n```text\nhi\n```\n");
+    ///
+    /// let results = config.run_document(&document, false);
+    ///
+    /// assert!(matches!(results[0].get_status(), BlockStatus::Ran(_)));
+    /// assert!(matches!(results[1].get_status(), BlockStatus::Skipped(_)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run_document(&mut self, document: &Document, sessions: bool) -> Vec<BlockResult> {
+        self.run_blocks(document, document.get_blocks().iter(), sessions)
+    }
+
+    /// Runs the code block containing `line`, starting at 1
+    ///
+    /// If the block is in a session, the blocks before it in the session are run first
+    ///
+    /// ```
+    /// use quickermd::QuickerMD;
+    /// use quickermd::document::Document;
+    /// use std::path::PathBuf;
+    /// # fn main() -> Result<(), quickermd::QuickerError> {
+    /// let mut config = QuickerMD::from_file_path(PathBuf::from("../examples/config.toml"))?;
+    /// let document = Document::parse("# Title\n\n```py\nprint('hi')\n```\n");
+    ///
+    /// let result = config.run_at_line(&document, 4, false)?;
+    ///
+    /// assert_eq!((result.get_start(), result.get_end()), (3, 5));
+    /// assert!(config.run_at_line(&document, 1, false).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run_at_line(
+        &mut self,
+        document: &Document,
+        line: usize,
+        sessions: bool,
+    ) -> Result<BlockResult, QuickerError> {
+        let block = document
+            .get_block_at_line(line)
+            .ok_or(QuickerError::NoBlockAtLine(line))?;
+
+        let mut results = match self.get_session_key(block, sessions) {
+            Some(key) => {
+                let session_blocks: Vec<&CodeBlock> = document
+                    .get_blocks()
+                    .iter()
+                    .take_while(|other| other.get_start() <= block.get_start())
+                    .filter(|other| self.get_session_key(other, sessions).as_ref() == Some(&key))
+                    .collect();
+
+                self.run_blocks(document, session_blocks.into_iter(), sessions)
+            }
+            None => self.run_blocks(document, [block].into_iter(), sessions),
+        };
+
+        Ok(results.pop().expect("The block at the line is always run"))
+    }
+
+    /// Returns the session `block` runs in, along with its language, so that
+    /// every alias of a language shares its sessions
+    fn get_session_key(&self, block: &CodeBlock, sessions: bool) -> Option<(String, String)> {
+        let name = block.get_session_name(sessions)?;
+        let lang = self
+            .resolve_lang(block.get_lang())
+            .unwrap_or_else(|_| block.get_lang().to_string());
+
+        Some((lang, name.to_string()))
+    }
+
+    fn run_blocks<'block>(
+        &mut self,
+        document: &Document,
+        blocks: impl Iterator<Item = &'block CodeBlock>,
+        sessions: bool,
+    ) -> Vec<BlockResult> {
+        let artifacts_dir = document.get_artifacts_dir();
+        let block_artifacts_dir = |block: &CodeBlock| {
+            artifacts_dir
+                .as_ref()
+                .map(|dir| dir.join(document.get_artifacts_name(block)))
+        };
+        let mut results = Vec::new();
+        let mut running_sessions: HashMap<(String, String), Session> = HashMap::new();
+
+        for block in blocks {
+            let status = if block.get_lang().is_empty() {
+                BlockStatus::Skipped("No language".to_string())
+            } else if block.get_attributes().is_ignored() {
+                BlockStatus::Skipped("Ignored".to_string())
+            } else if self.config.get_lang_conf(block.get_lang()).is_none() {
+                BlockStatus::Skipped(format!("No config for `{}`", block.get_lang()))
+            } else {
+                let result = match self.get_session_key(block, sessions) {
+                    Some(key) => {
+                        let session = running_sessions.entry(key).or_default();
+
+                        self.run_block_with_input(
+                            block,
+                            session.get_input(block),
+                            block_artifacts_dir(block),
+                        )
+                        .map(|output| session.push(block, &output))
+                    }
+                    None => self.run_block_with_input(
+                        block,
+                        block.get_input(),
+                        block_artifacts_dir(block),
+                    ),
+                };
+
+                match result {
+                    Ok(output) => BlockStatus::Ran(output),
+                    Err(e) => BlockStatus::Failed(e.to_string()),
+                }
+            };
+
+            results.push(BlockResult::new(block, status));
+        }
+
+        results
     }
 }
 
@@ -137,6 +434,17 @@ mod tests {
         QuickerMD::from_file_path(std::path::PathBuf::from("../examples/config.toml")).unwrap()
     }
 
+    /// Loads `config` from a file in a new directory, which lives as long as the
+    /// returned `TempDir`
+    fn quicker_from_str(config: &str) -> (tempfile::TempDir, QuickerMD) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, config).unwrap();
+
+        let quicker = QuickerMD::from_file_path(path).unwrap();
+        (dir, quicker)
+    }
+
     #[test]
     fn it_works() {
         let mut quicker = get_example_config();
@@ -184,6 +492,342 @@ Hello, world!
         )
     }
 
+    #[test]
+    fn it_only_compiles_with_no_run() {
+        let mut quicker = get_example_config();
+
+        let output = quicker
+            .run_with_options(
+                "c",
+                r#"printf("Hello, world!\n");"#.to_string(),
+                &RunOptions::new(true, None),
+            )
+            .unwrap();
+
+        assert_eq!(output.get_exit_code(), 0);
+        assert!(!output.has_stdout());
+    }
+
+    #[test]
+    fn it_builds_up_input_in_a_session() {
+        let mut quicker = get_example_config();
+        let document = document::Document::parse(
+            "```py\nx = 1\nprint(x)\n```\n```py\nprint(x + 1)\n```\n```py session=other\nprint(3)\n```\n",
+        );
+
+        let stdout = |result: &BlockResult| match result.get_status() {
+            BlockStatus::Ran(output) => output.get_stdout().replace("\r", ""),
+            _ => panic!("Block did not run"),
+        };
+
+        let results = quicker.run_document(&document, true);
+        assert_eq!(stdout(&results[0]), "1\n");
+        assert_eq!(stdout(&results[1]), "2\n");
+        assert_eq!(stdout(&results[2]), "3\n");
+
+        // Without sessions, `x` is not defined in the second block
+        let results = quicker.run_document(&document, false);
+        assert!(matches!(
+            results[1].get_status(),
+            BlockStatus::Ran(output) if output.get_exit_code() != 0
+        ));
+    }
+
+    #[test]
+    fn it_shares_sessions_between_aliases() {
+        let (_dir, mut quicker) = quicker_from_str(
+            r#"
+[langs.py]
+command = ["python3"]
+redir_input = true
+aliases = ["python3", "py3"]
+"#,
+        );
+        let document = document::Document::parse(
+            "```python3 session\nx = 1\n```\n```py3 session\nprint(x)\n```\n",
+        );
+
+        let results = quicker.run_document(&document, false);
+        match results[1].get_status() {
+            BlockStatus::Ran(output) => assert_eq!(output.get_stdout().trim(), "1"),
+            _ => panic!("Block did not run"),
+        }
+
+        let result = quicker.run_at_line(&document, 5, true).unwrap();
+        match result.get_status() {
+            BlockStatus::Ran(output) => assert_eq!(output.get_stdout().trim(), "1"),
+            _ => panic!("Block did not run"),
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_checks_blocks_killed_by_a_signal() {
+        let mut quicker = get_example_config();
+        let crash = "import os, signal\nos.kill(os.getpid(), signal.SIGSEGV)";
+        let document = document::Document::parse(&format!(
+            "```py\n{}\n```\n```py should_fail\n{}\n```\n",
+            crash, crash
+        ));
+
+        let results = quicker.run_document(&document, false);
+        let blocks = document.get_blocks();
+
+        assert!(matches!(
+            results[0].get_status(),
+            BlockStatus::Ran(output) if output.get_exit_code() == 128 + 11
+        ));
+        assert!(matches!(
+            results[0].check(&blocks[0]),
+            document::CheckStatus::Failed(_)
+        ));
+
+        // A crash is the failure a `should_fail` block expects
+        assert_eq!(results[1].check(&blocks[1]), document::CheckStatus::Passed);
+    }
+
+    #[test]
+    fn it_runs_the_session_before_the_block_at_a_line() {
+        let mut quicker = get_example_config();
+        let document = document::Document::parse(
+            "```py session\nx = 1\n```\n```py\nprint('other')\n```\n```py session\nprint(x)\n```\n",
+        );
+
+        let result = quicker.run_at_line(&document, 8, false).unwrap();
+
+        assert_eq!((result.get_start(), result.get_end()), (7, 9));
+        match result.get_status() {
+            BlockStatus::Ran(output) => assert_eq!(output.get_stdout().replace("\r", ""), "1\n"),
+            _ => panic!("Block did not run"),
+        }
+    }
+
+    #[test]
+    fn it_copies_images_written_to_the_artifacts_dir() {
+        let mut quicker = get_example_config();
+        let dir = tempfile::tempdir().unwrap();
+        let artifacts = dir.path().join("block-1");
+
+        std::fs::create_dir(&artifacts).unwrap();
+        std::fs::write(artifacts.join("notes.txt"), "").unwrap();
+        std::fs::write(artifacts.join("photo.png"), "").unwrap();
+
+        let mut options = RunOptions::default();
+        options.set_artifacts_dir(Some(artifacts.clone()));
+
+        let mut run = |image: &str| {
+            let input = format!(
+                r#"const fs = require("fs");
+fs.writeFileSync("{{{{ARTIFACTS}}}}/{}", "");
+fs.writeFileSync(process.env.QUICKERMD_ARTIFACTS + "/log.txt", "");"#,
+                image
+            );
+
+            quicker.run_with_options("js", input, &options).unwrap()
+        };
+
+        let output = run("plot.png");
+        assert_eq!(output.get_exit_code(), 0, "{}", output.get_stderr());
+        assert_eq!(output.get_artifacts(), &vec![artifacts.join("plot.png")]);
+        assert!(artifacts.join("plot.png").exists());
+        assert!(!artifacts.join("log.txt").exists());
+
+        // Only the images of the previous run are replaced
+        let output = run("chart.png");
+        assert_eq!(output.get_artifacts(), &vec![artifacts.join("chart.png")]);
+        assert!(!artifacts.join("plot.png").exists());
+        assert!(artifacts.join("notes.txt").exists());
+        assert!(artifacts.join("photo.png").exists());
+    }
+
+    #[test]
+    fn it_runs_commands_with_the_environment_and_cwd_of_the_language() {
+        let config = format!(
+            r#"
+[langs.js]
+command = ["node"]
+redir_input = true
+env = {{ GREETING = "hello" }}
+path_prepend = ["bin"]
+cwd = "temp"
+
+[langs.bare]
+command = ["node"]
+redir_input = true
+clear_env = true
+env = {{ PATH = {:?} }}
+"#,
+            std::env::var("PATH").unwrap()
+        );
+        let (dir, mut quicker) = quicker_from_str(&config);
+
+        let output = quicker
+            .run(
+                "js",
+                r#"const fs = require("fs"), path = require("path");
+const temp = fs.realpathSync(path.dirname(process.env.QUICKERMD_ARTIFACTS));
+console.log(process.env.GREETING, fs.realpathSync(process.cwd()) === temp);
+console.log(process.env.PATH.split(path.delimiter)[0]);"#
+                    .to_string(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            output.get_stdout().replace("\r", ""),
+            format!("hello true\n{}\n", dir.path().join("bin").display())
+        );
+
+        let output = quicker
+            .run(
+                "bare",
+                "console.log(Object.keys(process.env).sort().join())".to_string(),
+            )
+            .unwrap();
+
+        assert_eq!(output.get_stdout().trim(), "PATH,QUICKERMD_ARTIFACTS");
+    }
+
+    #[test]
+    fn it_writes_the_files_of_the_language() {
+        let (_dir, mut quicker) = quicker_from_str(
+            r#"
+[langs.js]
+command = ["node", "{{DIR}}/src/main.js"]
+run = false
+
+[langs.js.files]
+"src/main.js" = "const greet = require('../lib/greet.js');\n{{INPUT}}"
+"lib/greet.js" = "module.exports = (name) => `hello, $${name}`;"
+"#,
+        );
+
+        let output = quicker
+            .run("js", "console.log(greet('files'))".to_string())
+            .unwrap();
+
+        assert_eq!(output.get_stdout().trim(), "hello, files");
+    }
+
+    #[test]
+    fn it_keeps_the_files_of_a_persistent_workspace() {
+        let (_dir, mut quicker) = quicker_from_str(
+            r#"
+[langs.quickermd-test-workspace]
+command = ["node"]
+redir_input = true
+workspace = "persistent"
+"#,
+        );
+
+        let workspaces = tempfile::tempdir().unwrap();
+        let mut options = RunOptions::default();
+        options.set_workspace_root(Some(workspaces.path().to_path_buf()));
+
+        let input = r#"const fs = require("fs"), count = "{{DIR}}/count";
+const runs = fs.existsSync(count) ? Number(fs.readFileSync(count, "utf8")) + 1 : 1;
+fs.writeFileSync(count, String(runs));
+console.log(runs);"#;
+
+        let runs: Vec<String> = (0..2)
+            .map(|_| {
+                quicker
+                    .run_with_options("quickermd-test-workspace", input.to_string(), &options)
+                    .unwrap()
+                    .get_stdout()
+                    .trim()
+                    .to_string()
+            })
+            .collect();
+
+        assert_eq!(runs, vec!["1", "2"]);
+        assert!(workspaces
+            .path()
+            .join("quickermd-test-workspace")
+            .join("count")
+            .exists());
+    }
+
+    #[test]
+    fn it_names_the_source_and_output_files() {
+        let (_dir, mut quicker) = quicker_from_str(
+            r#"
+[langs.js]
+command = ["node", "-e", "require('fs').copyFileSync(process.argv[1], process.argv[2])", "{{IN}}", "{{OUT}}"]
+run = ["node", "{{OUT}}", "{{STEM}}"]
+template = "{{INPUT}}"
+filename = "snippet.main.cjs"
+output_name = "compiled.cjs"
+"#,
+        );
+
+        let output = quicker
+            .run(
+                "js",
+                r#"const fs = require("fs"), path = require("path");
+console.log(path.basename(__filename), process.argv[2]);
+console.log(fs.existsSync("{{DIR}}/snippet.main.cjs"));"#
+                    .to_string(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            output.get_stdout().replace("\r", ""),
+            "compiled.cjs snippet\ntrue\n"
+        );
+    }
+
+    /// Runs `input` with the preset `name`, or returns `None` when its
+    /// program is not installed
+    fn run_preset(name: &str, input: &str) -> Option<String> {
+        let (_dir, mut quicker) =
+            quicker_from_str(&format!("[langs.{}]\npreset = \"{}\"\n", name, name));
+        let program = quicker
+            .get_config_for_lang(name)
+            .unwrap()
+            .get_command_name();
+
+        if toolchains::find_program(&program).is_none() {
+            eprintln!(
+                "Skipping the `{}` preset, `{}` is not on PATH",
+                name, program
+            );
+            return None;
+        }
+
+        let output = quicker.run(name, input.to_string()).unwrap();
+        assert_eq!(output.get_exit_code(), 0, "{}", output.get_stderr());
+
+        Some(output.get_stdout().replace("\r", ""))
+    }
+
+    #[test]
+    fn it_runs_the_c_preset() {
+        if let Some(stdout) = run_preset("c", r#"printf("%d\n", 6 * 7);"#) {
+            assert_eq!(stdout, "42\n");
+        }
+    }
+
+    #[test]
+    fn it_runs_the_js_preset() {
+        if let Some(stdout) = run_preset("js", "console.log(6 * 7)") {
+            assert_eq!(stdout, "42\n");
+        }
+    }
+
+    #[test]
+    fn it_runs_the_py_preset() {
+        if let Some(stdout) = run_preset("py", "print(6 * 7)") {
+            assert_eq!(stdout, "42\n");
+        }
+    }
+
+    #[test]
+    fn it_runs_the_bash_preset() {
+        if let Some(stdout) = run_preset("bash", "echo $((6 * 7))") {
+            assert_eq!(stdout, "42\n");
+        }
+    }
+
     #[test]
     fn it_outputs_as_json() {
         let mut quicker = get_example_config();
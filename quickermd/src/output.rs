@@ -1,14 +1,19 @@
+use std::path::PathBuf;
 use std::process::ExitStatus;
 
 use crate::utils::u8_to_str;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Output {
     format: OutputType,
     stdout: String,
     stderr: String,
     code: i32,
+
+    /// The image files the run wrote to `{{ARTIFACTS}}`, after being copied out of it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    artifacts: Vec<PathBuf>,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -40,6 +45,25 @@ impl From<String> for OutputType {
     }
 }
 
+/// Returns the exit code of `status`, or `128 + signal` when the process was
+/// killed by a signal, the same code a shell reports
+fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    -1
+}
+
 impl Output {
     /// Creates a new instance of `Output`
     pub fn new(
@@ -53,20 +77,17 @@ impl Output {
             stdout: stdout.unwrap_or("".to_string()),
             stderr: stderr.unwrap_or("".to_string()),
             code,
+            artifacts: Vec::new(),
         }
     }
     /// Creates a new instance of `Output` using a vector of bytes
-    pub fn from_u8(
-        format: OutputType,
-        stdout: &Vec<u8>,
-        stderr: &Vec<u8>,
-        code: ExitStatus,
-    ) -> Self {
+    pub fn from_u8(format: OutputType, stdout: &[u8], stderr: &[u8], code: ExitStatus) -> Self {
         Output {
             format,
-            code: code.code().unwrap_or(0),
+            code: exit_code(code),
             stdout: u8_to_str(stdout),
             stderr: u8_to_str(stderr),
+            artifacts: Vec::new(),
         }
     }
     /// Checks if `stdout` is non-empty
@@ -122,18 +143,30 @@ impl Output {
         self.code
     }
 
+    /// Returns the copied `artifacts`
+    pub fn get_artifacts(&self) -> &Vec<PathBuf> {
+        &self.artifacts
+    }
+
+    /// Sets the copied `artifacts`
+    pub fn set_artifacts(&mut self, artifacts: Vec<PathBuf>) {
+        self.artifacts = artifacts;
+    }
+
     /// Returns the current `format`
     pub fn get_format(&self) -> OutputType {
         self.format.clone()
     }
 }
 
-impl ToString for Output {
-    fn to_string(&self) -> String {
-        match self.format {
+impl std::fmt::Display for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self.format {
             OutputType::JSON => serde_json::to_string(self).unwrap(),
             OutputType::JsonPretty => serde_json::to_string_pretty(self).unwrap(),
             OutputType::Raw => self.raw_to_string(),
-        }
+        };
+
+        write!(f, "{}", str)
     }
 }
@@ -0,0 +1,52 @@
+//! Language definitions shipped with `QuickerMD`, used by a `[langs.*]`
+//! table with `preset = "name"`
+
+use std::path::PathBuf;
+use toml::Table;
+
+/// Every preset, by name, in the same format as a `[langs.*]` table
+const PRESETS: &[(&str, &str)] = &[
+    ("bash", include_str!("presets/bash.toml")),
+    ("c", include_str!("presets/c.toml")),
+    ("clang", include_str!("presets/clang.toml")),
+    ("cpp", include_str!("presets/cpp.toml")),
+    ("go", include_str!("presets/go.toml")),
+    ("haskell", include_str!("presets/haskell.toml")),
+    ("java", include_str!("presets/java.toml")),
+    ("js", include_str!("presets/js.toml")),
+    ("lua", include_str!("presets/lua.toml")),
+    ("py", include_str!("presets/py.toml")),
+    ("rb", include_str!("presets/rb.toml")),
+    ("rust", include_str!("presets/rust.toml")),
+    ("ts", include_str!("presets/ts.toml")),
+    ("zig", include_str!("presets/zig.toml")),
+];
+
+/// Returns the name of every preset
+///
+/// ```
+/// use quickermd::presets;
+///
+/// assert!(presets::names().contains(&"rust"));
+/// ```
+pub fn names() -> Vec<&'static str> {
+    PRESETS.iter().map(|(name, _)| *name).collect()
+}
+
+/// Returns the settings of a preset, as written in its file
+pub fn get_source(name: &str) -> Option<&'static str> {
+    PRESETS
+        .iter()
+        .find(|(preset, _)| *preset == name)
+        .map(|(_, source)| *source)
+}
+
+/// Returns the settings of a preset
+pub fn get_table(name: &str) -> Option<Table> {
+    get_source(name).map(|source| toml::from_str::<Table>(source).expect("The presets are valid"))
+}
+
+/// Returns the source shown for the settings that come from a preset
+pub fn get_source_path(name: &str) -> PathBuf {
+    PathBuf::from(format!("<preset:{}>", name))
+}
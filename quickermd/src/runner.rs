@@ -1,16 +1,54 @@
-use std::io::Write;
+use std::ffi::{OsStr, OsString};
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::process::Stdio;
-use std::{path::PathBuf, process::Command};
+use std::time::{Duration, Instant};
+use std::{path::Path, process::Command};
 use variable_parser::VariableParser;
 
+use crate::error::QuickerError;
 use crate::output::{OutputType, Output};
 use crate::user_config::*;
 
+/// Replaced with a directory that commands and templates can write images to
+const ARTIFACTS_VARIABLE: &str = "{{ARTIFACTS}}";
+
+/// The environment variable holding the artifacts directory
+const ARTIFACTS_ENV: &str = "QUICKERMD_ARTIFACTS";
+
+/// Lists the images copied to the artifacts directory, so that the next run
+/// only replaces those
+const ARTIFACTS_MANIFEST: &str = ".quickermd-artifacts";
+
+const IMAGE_EXTENSIONS: [&str; 7] = ["png", "jpg", "jpeg", "gif", "svg", "webp", "bmp"];
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Replaced with the temporary directory of the run, holding every file it writes
+const DIR_VARIABLE: &str = "{{DIR}}";
+
+/// Replaced with the name of the source file, up to its first `.`
+const STEM_VARIABLE: &str = "{{STEM}}";
+
+/// The name of the file the template is written to when `filename` is not set
+const DEFAULT_SOURCE_STEM: &str = "tmp";
+
+/// The name of the file `{{OUT}}` refers to when `output_name` is not set
+const DEFAULT_OUTPUT_NAME: &str = "out";
+
 pub struct QuickMDRunner<'runner> {
     template: &'runner Template,
     lang_conf: &'runner LanguageConfig,
-    config: &'runner Config,
     lang: &'runner str,
+    no_run: bool,
+    timeout: Option<Duration>,
+    artifacts_dir: Option<PathBuf>,
+    workspace_root: Option<PathBuf>,
 }
 
 impl<'runner> QuickMDRunner<'runner> {
@@ -18,30 +56,155 @@ impl<'runner> QuickMDRunner<'runner> {
         lang: &'runner str,
         template: &'runner Template,
         lang_conf: &'runner LanguageConfig,
-        config: &'runner Config,
     ) -> Self {
         Self {
             lang,
             template,
             lang_conf,
-            config,
+            no_run: false,
+            timeout: lang_conf.get_timeout(),
+            artifacts_dir: None,
+            workspace_root: None,
+        }
+    }
+
+    /// Only runs `command`, skipping the run step
+    pub fn set_no_run(&mut self, no_run: bool) {
+        self.no_run = no_run;
+    }
+
+    /// Sets the time limit of each spawned command
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Copies the images written to `{{ARTIFACTS}}` into `dir`, replacing
+    /// the images of a previous run
+    pub fn set_artifacts_dir(&mut self, dir: Option<PathBuf>) {
+        self.artifacts_dir = dir;
+    }
+
+    /// Keeps persistent workspaces in `root`, instead of the cache directory
+    pub fn set_workspace_root(&mut self, root: Option<PathBuf>) {
+        self.workspace_root = root;
+    }
+
+    /// Returns the path of the source file, relative to the directory of the run
+    fn source_name(&self) -> String {
+        self.lang_conf.get_filename().unwrap_or_else(|| {
+            format!(
+                "{}.{}",
+                DEFAULT_SOURCE_STEM,
+                self.lang_conf
+                    .get_extension()
+                    .unwrap_or(self.lang.to_string())
+            )
+        })
+    }
+
+    /// Returns the name of the source file up to its first `.`,
+    /// i.e. `Main` for `Main.java` and `snippet` for `snippet.main.kts`
+    fn source_stem(&self) -> String {
+        let name = self.source_name();
+        let file_name = Path::new(&name)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(DEFAULT_SOURCE_STEM);
+
+        file_name.split('.').next().unwrap_or_default().to_string()
+    }
+
+    /// Replaces `{{DIR}}`, `{{STEM}}` and `{{ARTIFACTS}}` anywhere in `text`,
+    /// i.e. `--out={{ARTIFACTS}}/plot.png`
+    fn replace_variables(&self, text: &str, dir: &str, artifacts: &str) -> String {
+        text.replace(ARTIFACTS_VARIABLE, artifacts)
+            .replace(DIR_VARIABLE, dir)
+            .replace(STEM_VARIABLE, &self.source_stem())
+    }
+
+    fn replace_variables_in_args(&self, args: &mut [String], dir: &str, artifacts: &str) {
+        for arg in args.iter_mut() {
+            *arg = self.replace_variables(arg, dir, artifacts);
         }
     }
-    pub fn start(&mut self) -> std::io::Result<Output> {
-        if self.lang_conf.get_redir_input() {
-            return self.redirect_input();
+
+    pub fn start(&mut self) -> Result<Output, QuickerError> {
+        match self.lang_conf.get_workspace() {
+            Workspace::Temp => {
+                let tmp_dir = tempfile::tempdir()?;
+                let ret = self.run_in(tmp_dir.path());
+
+                _ = tmp_dir.close();
+
+                ret
+            }
+            Workspace::Persistent => {
+                let root = match &self.workspace_root {
+                    Some(root) => root.clone(),
+                    None => Config::get_workspace_root()?,
+                };
+                let dir = Config::get_workspace_dir(&root, self.lang);
+                std::fs::create_dir_all(&dir)?;
+
+                // Concurrent runs of the language wait for each other,
+                // as they share the files of the directory
+                let lock = OpenOptions::new()
+                    .create(true)
+                    .truncate(false)
+                    .write(true)
+                    .open(dir.join(".lock"))?;
+                lock.lock()?;
+
+                let ret = self.run_in(&dir);
+
+                _ = lock.unlock();
+
+                ret
+            }
         }
+    }
+
+    /// Runs the input with `dir` holding its files
+    fn run_in(&self, dir: &Path) -> Result<Output, QuickerError> {
+        let artifacts = dir.join("artifacts");
+
+        // A persistent workspace still has the images of the previous run
+        if artifacts.exists() {
+            std::fs::remove_dir_all(&artifacts)?;
+        }
+
+        std::fs::create_dir(&artifacts)?;
+        self.write_files(dir, &artifacts)?;
+
+        let result = if self.lang_conf.get_redir_input() {
+            self.redirect_input(dir, &artifacts)
+        } else {
+            self.run_template(dir, &artifacts)
+        };
 
-        let tmp_dir = tempfile::tempdir()?;
-        let tmp_path = tmp_dir.path().join(format!(
-            "tmp.{}",
+        result.and_then(|mut output| {
+            output.set_artifacts(self.collect_artifacts(&artifacts)?);
+            Ok(output)
+        })
+    }
+
+    fn run_template(&self, tmp_dir: &Path, artifacts: &Path) -> Result<Output, QuickerError> {
+        let tmp_path = tmp_dir.join(self.source_name());
+
+        let outfile = tmp_dir.join(
             self.lang_conf
-                .get_extension()
-                .unwrap_or(self.lang.to_string())
-        ));
+                .get_output_name()
+                .unwrap_or(DEFAULT_OUTPUT_NAME.to_string()),
+        );
+        let dir = tmp_dir.to_str().unwrap();
+        let artifacts = artifacts.to_str().unwrap();
 
-        let outfile = tmp_dir.path().join("out");
-        self.template.write_to_file(tmp_path.clone())?;
+        if let Some(parent) = tmp_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let contents = self.replace_variables(&self.template.to_string(), dir, artifacts);
+        std::fs::write(&tmp_path, contents)?;
 
         let variables = vec![
             ("{{IN}}", tmp_path.to_str().unwrap()),
@@ -55,15 +218,17 @@ impl<'runner> QuickMDRunner<'runner> {
         let mut args = self.lang_conf.get_command_args();
 
         parser.parse_with_tracker(&mut args);
+        self.replace_variables_in_args(&mut args, dir, artifacts);
 
         let consumed_input = parser.had_used_var("{{INPUT}}");
 
-        let output = Command::new(cmd_name).args(args).output()?;
-
-        if !output.status.success() || consumed_input || self.lang_conf.explicit_no_run() {
-            drop(outfile);
-            _ = tmp_dir.close();
+        let output = self.spawn(self.command(cmd_name, tmp_dir, artifacts).args(args), None)?;
 
+        if !output.status.success()
+            || consumed_input
+            || self.lang_conf.explicit_no_run()
+            || self.no_run
+        {
             return Ok(Output::from_u8(
                 OutputType::Raw,
                 &output.stdout,
@@ -72,54 +237,50 @@ impl<'runner> QuickMDRunner<'runner> {
             ));
         }
 
-        let ret = self.run_explicit(&outfile, &parser);
-
-        drop(outfile);
-        _ = tmp_dir.close();
-
-        ret
+        self.run_explicit(&outfile, &parser, tmp_dir, artifacts)
     }
 
     fn run_explicit(
         &self,
-        file: &PathBuf,
+        file: &Path,
         variables: &'runner VariableParser<&str>,
-    ) -> std::io::Result<Output> {
-        let output_file = format!("{}", file.to_str().unwrap());
+        tmp_dir: &Path,
+        artifacts: &str,
+    ) -> Result<Output, QuickerError> {
+        let output_file = file.to_str().unwrap().to_string();
         let output;
 
         if let Some((exe_command, mut args)) = self.lang_conf.get_run_command(output_file.clone()) {
             variables.parse_string_vec(&mut args);
-            output = Command::new(exe_command).args(args).output()?;
+            self.replace_variables_in_args(&mut args, tmp_dir.to_str().unwrap(), artifacts);
+            output = self.spawn(
+                self.command(exe_command, tmp_dir, artifacts).args(args),
+                None,
+            )?;
         } else {
-            output = Command::new(output_file).output()?;
+            output = self.spawn(&mut self.command(output_file, tmp_dir, artifacts), None)?;
         }
 
         Ok(Output::from_u8(OutputType::Raw, &output.stdout, &output.stderr, output.status))
     }
 
-    fn redirect_input(&self) -> std::io::Result<Output> {
-        let cmd_name = self.lang_conf.get_command_name();
-        let args = self.lang_conf.get_command_args();
-
-        let mut child = Command::new(cmd_name)
-            .args(args)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
-
-        let mut stdin = child.stdin.take().expect("Failed to open stdin");
-
-        let input = self.template.get_input();
+    fn redirect_input(&self, tmp_dir: &Path, artifacts: &Path) -> Result<Output, QuickerError> {
+        // There is nothing to compile when the input is redirected
+        if self.no_run {
+            return Ok(Output::new(OutputType::Raw, None, None, 0));
+        }
 
-        std::thread::spawn(move || {
-            stdin
-                .write_all(input.join("\n").as_bytes())
-                .expect("Failed to write stdin");
-        });
+        let dir = tmp_dir.to_str().unwrap();
+        let artifacts = artifacts.to_str().unwrap();
+        let cmd_name = self.lang_conf.get_command_name();
+        let mut args = self.lang_conf.get_command_args();
+        self.replace_variables_in_args(&mut args, dir, artifacts);
 
-        let output = child.wait_with_output().expect("Failed to create output");
+        let input = self.replace_variables(&self.template.get_input().join("\n"), dir, artifacts);
+        let output = self.spawn(
+            self.command(cmd_name, tmp_dir, artifacts).args(args),
+            Some(input),
+        )?;
 
         Ok(Output::from_u8(
             OutputType::Raw,
@@ -128,4 +289,203 @@ impl<'runner> QuickMDRunner<'runner> {
             output.status,
         ))
     }
+
+    /// Writes the `files` of the language into `tmp_dir`, with the input in
+    /// place of `{{INPUT}}`
+    ///
+    /// Unchanged files are not written again, so tools that compare
+    /// modification times don't rebuild them in a persistent workspace
+    fn write_files(&self, tmp_dir: &Path, artifacts: &Path) -> Result<(), QuickerError> {
+        let dir = tmp_dir.to_str().unwrap();
+        let artifacts = artifacts.to_str().unwrap();
+        let input = self.template.get_input().join("\n");
+
+        for (path, contents) in self.lang_conf.get_files() {
+            let path = tmp_dir.join(path);
+
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            let contents =
+                self.replace_variables(&contents.replace("{{INPUT}}", &input), dir, artifacts);
+            if std::fs::read_to_string(&path).ok().as_ref() != Some(&contents) {
+                std::fs::write(path, contents)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Creates a command for `program`, with the environment and working
+    /// directory of the language, and the artifacts directory in its environment
+    fn command<S: AsRef<OsStr>>(&self, program: S, tmp_dir: &Path, artifacts: &str) -> Command {
+        let mut command = Command::new(program);
+
+        if self.lang_conf.clears_env() {
+            command.env_clear();
+        }
+
+        command.envs(self.lang_conf.get_env());
+
+        // `PATH` is also used to find `program`
+        let path_prepend = self.lang_conf.get_path_prepend();
+        if !path_prepend.is_empty() {
+            let path = match self.lang_conf.get_env().get("PATH") {
+                Some(path) => Some(OsString::from(path)),
+                None if self.lang_conf.clears_env() => None,
+                None => std::env::var_os("PATH"),
+            };
+
+            let dirs = path_prepend
+                .iter()
+                .map(PathBuf::from)
+                .chain(path.iter().flat_map(std::env::split_paths));
+
+            if let Ok(path) = std::env::join_paths(dirs) {
+                command.env("PATH", path);
+            }
+        }
+
+        command.env(ARTIFACTS_ENV, artifacts);
+
+        match self.lang_conf.get_cwd() {
+            WorkingDir::Temp => {
+                command.current_dir(tmp_dir);
+            }
+            WorkingDir::Invocation => {}
+            WorkingDir::Path(dir) => {
+                command.current_dir(dir);
+            }
+        }
+
+        command
+    }
+
+    /// Copies the images in `artifacts` to the artifacts directory,
+    /// returning their new paths
+    ///
+    /// Only the images copied by the previous run are removed, the directory
+    /// and the other files in it are left alone
+    fn collect_artifacts(&self, artifacts: &Path) -> std::io::Result<Vec<PathBuf>> {
+        let Some(dir) = &self.artifacts_dir else {
+            return Ok(Vec::new());
+        };
+
+        let mut images: Vec<PathBuf> = std::fs::read_dir(artifacts)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && is_image(path))
+            .collect();
+        images.sort();
+
+        let manifest = dir.join(ARTIFACTS_MANIFEST);
+        if let Ok(previous) = std::fs::read_to_string(&manifest) {
+            for name in previous.lines() {
+                let image = dir.join(name);
+
+                // The manifest only ever lists image names, but it is still a file anyone can edit
+                if Path::new(name).file_name() == Some(OsStr::new(name)) && is_image(&image) {
+                    _ = std::fs::remove_file(image);
+                }
+            }
+
+            std::fs::remove_file(&manifest)?;
+        }
+
+        if images.is_empty() {
+            return Ok(images);
+        }
+
+        std::fs::create_dir_all(dir)?;
+
+        let copied = images
+            .into_iter()
+            .map(|image| {
+                let copied = dir.join(image.file_name().unwrap_or_default());
+                std::fs::copy(&image, &copied)?;
+                Ok(copied)
+            })
+            .collect::<std::io::Result<Vec<PathBuf>>>()?;
+
+        let names: Vec<String> = copied
+            .iter()
+            .filter_map(|image| image.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .collect();
+        std::fs::write(manifest, names.join("\n"))?;
+
+        Ok(copied)
+    }
+
+    /// Spawns `command`, writing `input` to its stdin, and waits for it
+    /// to finish within the time limit
+    fn spawn(
+        &self,
+        command: &mut Command,
+        input: Option<String>,
+    ) -> Result<std::process::Output, QuickerError> {
+        let mut child = command
+            .stdin(if input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|source| QuickerError::Spawn {
+                program: command.get_program().to_string_lossy().to_string(),
+                source,
+            })?;
+
+        if let Some(input) = input {
+            let mut stdin = child.stdin.take().expect("Failed to open stdin");
+
+            std::thread::spawn(move || {
+                // The child may exit without reading all of its input
+                _ = stdin.write_all(input.as_bytes());
+            });
+        }
+
+        let Some(timeout) = self.timeout else {
+            return Ok(child.wait_with_output()?);
+        };
+
+        let read_pipe = |mut pipe: Box<dyn Read + Send>| {
+            std::thread::spawn(move || {
+                let mut buf = Vec::new();
+                _ = pipe.read_to_end(&mut buf);
+                buf
+            })
+        };
+
+        let stdout = read_pipe(Box::new(
+            child.stdout.take().expect("Failed to open stdout"),
+        ));
+        let stderr = read_pipe(Box::new(
+            child.stderr.take().expect("Failed to open stderr"),
+        ));
+
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+
+            if start.elapsed() >= timeout {
+                _ = child.kill();
+                _ = child.wait();
+
+                return Err(QuickerError::Timeout(timeout));
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        };
+
+        Ok(std::process::Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        })
+    }
 }
@@ -0,0 +1,175 @@
+//! JSON Schemas of the config files, for the completion and validation of
+//! TOML editors
+//!
+//! The settings of each table are read from the serde type the table is
+//! deserialized into, so a setting added to the type without being described
+//! here is caught when its schema is built.
+
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_json::{json, Map, Value};
+use std::fmt::Display;
+
+/// The version of JSON Schema the schemas are written in
+const SCHEMA_VERSION: &str = "http://json-schema.org/draft-07/schema#";
+
+/// Returns the names of the settings `T` reads, as they are written in the file
+///
+/// ```
+/// use quickermd::schema;
+/// use quickermd::user_config::LanguageConfig;
+///
+/// assert!(schema::field_names::<LanguageConfig>().contains(&"command"));
+/// ```
+///
+/// # Panics
+/// When `T` is not deserialized from a table
+pub fn field_names<T: DeserializeOwned>() -> &'static [&'static str] {
+    let mut fields = None;
+    _ = T::deserialize(FieldNames(&mut fields));
+
+    fields.unwrap_or_else(|| panic!("`{}` is not a table", std::any::type_name::<T>()))
+}
+
+/// Describes a table deserialized into `T`, with the schema `describe` returns
+/// for each of its settings
+///
+/// # Panics
+/// When a setting of `T` is not described
+pub fn table<T: DeserializeOwned>(describe: impl Fn(&str) -> Option<Value>) -> Value {
+    let mut properties = Map::new();
+
+    for &name in field_names::<T>() {
+        let schema = describe(name).unwrap_or_else(|| {
+            panic!(
+                "The setting `{}` of `{}` is not described",
+                name,
+                std::any::type_name::<T>()
+            )
+        });
+
+        properties.insert(name.to_string(), schema);
+    }
+
+    json!({
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+/// Makes `schema` the schema of a whole file
+pub fn document(title: &str, mut schema: Value) -> Value {
+    schema["$schema"] = json!(SCHEMA_VERSION);
+    schema["title"] = json!(title);
+
+    schema
+}
+
+pub fn string(description: &str) -> Value {
+    json!({ "type": "string", "description": description })
+}
+
+pub fn boolean(description: &str) -> Value {
+    json!({ "type": "boolean", "description": description })
+}
+
+pub fn strings(description: &str) -> Value {
+    json!({
+        "type": "array",
+        "items": { "type": "string" },
+        "description": description,
+    })
+}
+
+/// A table of strings, by any key
+pub fn string_table(description: &str) -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": { "type": "string" },
+        "description": description,
+    })
+}
+
+/// One of `values`
+pub fn one_of(values: &[&str], description: &str) -> Value {
+    json!({ "enum": values, "description": description })
+}
+
+/// A deserializer that only records the fields of the struct it is asked for
+struct FieldNames<'a>(&'a mut Option<&'static [&'static str]>);
+
+#[derive(Debug)]
+struct NotATable;
+
+impl Display for NotATable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Only the fields of a table are read")
+    }
+}
+
+impl std::error::Error for NotATable {}
+
+impl de::Error for NotATable {
+    fn custom<T: Display>(_msg: T) -> Self {
+        NotATable
+    }
+}
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = NotATable;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(NotATable)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = Some(fields);
+
+        Err(NotATable)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Lang {
+        #[serde(rename = "command")]
+        compile_command: Vec<String>,
+
+        #[serde(skip)]
+        parsed: String,
+
+        redir_input: bool,
+    }
+
+    #[test]
+    fn it_describes_the_settings_of_a_type() {
+        assert_eq!(field_names::<Lang>(), ["command", "redir_input"]);
+
+        let schema = table::<Lang>(|setting| Some(string(setting)));
+        assert_eq!(schema["properties"]["command"]["description"], "command");
+        assert_eq!(schema["additionalProperties"], false);
+    }
+
+    #[test]
+    #[should_panic(expected = "The setting `redir_input`")]
+    fn it_fails_on_settings_that_are_not_described() {
+        table::<Lang>(|setting| (setting == "command").then(|| string(setting)));
+    }
+}
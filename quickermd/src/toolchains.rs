@@ -0,0 +1,209 @@
+//! Common toolchains, and the config written for them by `quicker_md init`
+
+use std::path::{Path, PathBuf};
+
+use crate::presets;
+
+/// A language that can be configured when its programs are installed
+#[derive(Debug)]
+pub struct Toolchain {
+    /// The name of the `[langs.*]` table
+    lang: &'static str,
+
+    /// The programs that must all be on `PATH`, the first one is shown
+    programs: &'static [&'static str],
+
+    /// The preset the `[langs.*]` table is written from
+    preset: &'static str,
+}
+
+/// Every known toolchain, the preferred one first when several configure a language
+const TOOLCHAINS: &[Toolchain] = &[
+    Toolchain {
+        lang: "c",
+        programs: &["gcc"],
+        preset: "c",
+    },
+    Toolchain {
+        lang: "c",
+        programs: &["clang"],
+        preset: "clang",
+    },
+    Toolchain {
+        lang: "cpp",
+        programs: &["g++"],
+        preset: "cpp",
+    },
+    Toolchain {
+        lang: "rust",
+        programs: &["rustc"],
+        preset: "rust",
+    },
+    Toolchain {
+        lang: "go",
+        programs: &["go"],
+        preset: "go",
+    },
+    Toolchain {
+        lang: "js",
+        programs: &["node"],
+        preset: "js",
+    },
+    Toolchain {
+        lang: "ts",
+        programs: &["deno"],
+        preset: "ts",
+    },
+    Toolchain {
+        lang: "py",
+        programs: &["python3"],
+        preset: "py",
+    },
+    Toolchain {
+        lang: "rb",
+        programs: &["ruby"],
+        preset: "rb",
+    },
+    Toolchain {
+        lang: "lua",
+        programs: &["lua"],
+        preset: "lua",
+    },
+    Toolchain {
+        lang: "java",
+        programs: &["java", "javac"],
+        preset: "java",
+    },
+    Toolchain {
+        lang: "haskell",
+        programs: &["ghc"],
+        preset: "haskell",
+    },
+    Toolchain {
+        lang: "zig",
+        programs: &["zig"],
+        preset: "zig",
+    },
+    Toolchain {
+        lang: "bash",
+        programs: &["bash"],
+        preset: "bash",
+    },
+];
+
+impl Toolchain {
+    pub fn get_lang(&self) -> &str {
+        self.lang
+    }
+
+    /// Returns the program the language is run with
+    pub fn get_program(&self) -> &str {
+        self.programs[0]
+    }
+
+    pub fn get_preset(&self) -> &str {
+        self.preset
+    }
+
+    /// Returns the `[langs.*]` table of the language, with its header
+    pub fn to_toml(&self) -> String {
+        let preset = presets::get_source(self.preset).expect("Every toolchain has a preset");
+
+        format!("[langs.{}]\n{}", self.lang, preset)
+    }
+}
+
+/// Returns every known toolchain
+pub fn all() -> &'static [Toolchain] {
+    TOOLCHAINS
+}
+
+/// Finds the toolchains installed on `PATH`, at most one for each language
+pub fn detect() -> Vec<&'static Toolchain> {
+    detect_with(|program| find_program(program).is_some())
+}
+
+/// Finds the toolchains whose programs are all `installed`, at most one for
+/// each language
+pub fn detect_with(installed: impl Fn(&str) -> bool) -> Vec<&'static Toolchain> {
+    let mut found: Vec<&'static Toolchain> = Vec::new();
+
+    for toolchain in TOOLCHAINS {
+        if found.iter().any(|other| other.lang == toolchain.lang) {
+            continue;
+        }
+
+        if toolchain.programs.iter().all(|program| installed(program)) {
+            found.push(toolchain);
+        }
+    }
+
+    found
+}
+
+/// Finds `program` in the directories of `PATH`
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(format!("{}{}", program, std::env::consts::EXE_SUFFIX)))
+        .find(|path| is_executable(path))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Writes a config with a `[langs.*]` table for each of `toolchains`
+pub fn generate_config(toolchains: &[&Toolchain]) -> String {
+    let entries: Vec<String> = toolchains
+        .iter()
+        .map(|toolchain| toolchain.to_toml())
+        .collect();
+
+    format!(
+        "# Written by `quicker_md init` for the toolchains found on PATH\n\n{}",
+        entries.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_config::Config;
+
+    #[test]
+    fn it_prefers_the_first_toolchain_of_a_language() {
+        let found = detect_with(|program| ["clang", "gcc", "java", "node"].contains(&program));
+        let found: Vec<(&str, &str)> = found
+            .iter()
+            .map(|toolchain| (toolchain.get_lang(), toolchain.get_program()))
+            .collect();
+
+        // `java` also needs `javac`
+        assert_eq!(found, vec![("c", "gcc"), ("js", "node")]);
+    }
+
+    #[test]
+    fn it_generates_a_valid_config_for_every_toolchain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        for toolchain in all() {
+            std::fs::write(&path, generate_config(&[toolchain])).unwrap();
+
+            let config = Config::get_config_from_path(path.clone())
+                .unwrap_or_else(|e| panic!("`{}`: {}", toolchain.get_program(), e));
+            assert!(config.get_lang_conf(toolchain.get_lang()).is_some());
+        }
+    }
+}
@@ -1,12 +1,39 @@
 use directories::ProjectDirs;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::io::prelude::*;
+use std::path::Path;
+use std::time::Duration;
 use std::{collections::HashMap, fs::File, path::PathBuf};
+use toml::{Table, Value};
+
+use crate::env;
+use crate::error::QuickerError;
+use crate::presets;
+use crate::schema;
+use crate::utils;
+use crate::validation::ConfigProblem;
+
+/// The name of the project-local config, merged over the global config
+pub const LOCAL_CONFIG_NAME: &str = ".quickermd.toml";
+
+/// The environment variable selecting the config file, in place of every other config
+pub const CONFIG_ENV: &str = "QUICKERMD_CONFIG";
+
+/// Used in place of the global config when it does not exist
+const FALLBACK_CONFIG: &str = include_str!("fallback_config.toml");
+
+/// The source shown for settings of the built-in fallback config
+const FALLBACK_SOURCE: &str = "<built-in>";
 
 /// The Config Struct
 #[derive(Deserialize, Debug)]
 pub struct Config {
     langs: HashMap<String, LanguageConfig>,
+
+    /// The file each setting came from, by its dotted key, i.e. `langs.c.command`
+    #[serde(skip)]
+    sources: BTreeMap<String, PathBuf>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -15,14 +42,42 @@ pub struct LanguageConfig {
     /// The user's defined template
     raw_template: Option<String>,
 
+    /// The file the template is read from, in place of `template`
+    template_file: Option<String>,
+
+    #[serde(default)]
+    /// Other files written next to the template, by their relative path
+    files: BTreeMap<String, String>,
+
     /// The prefix to output the data with,
     /// if the user wishes
     comment: Option<String>,
 
+    /// The prefix of lines that are run, but never displayed,
+    /// i.e. `# ` for setup lines in rust
+    hidden: Option<String>,
+
     /// The file extension to use when creating a file
     /// for this language
     extension: Option<String>,
 
+    /// The name of the file the template is written to, in place of
+    /// `tmp.<extension>`
+    filename: Option<String>,
+
+    /// The name of the file `{{OUT}}` refers to, in place of `out`
+    output_name: Option<String>,
+
+    #[serde(default)]
+    /// Other names the language can be looked up by
+    aliases: Vec<String>,
+
+    /// The language this language inherits its settings from
+    extends: Option<String>,
+
+    /// The built-in language definition this language starts from
+    preset: Option<String>,
+
     #[serde(rename = "command")]
     /// The command that will be ran
     /// as the first step
@@ -36,12 +91,56 @@ pub struct LanguageConfig {
     /// Whether to redirect input into compile_command
     redir_input: bool,
 
+    /// The time limit of each command, in seconds
+    timeout: Option<f64>,
+
+    #[serde(default)]
+    /// Environment variables set for every command
+    env: BTreeMap<String, String>,
+
+    #[serde(default)]
+    /// Directories searched for programs before the ones on `PATH`
+    path_prepend: Vec<String>,
+
+    #[serde(default)]
+    /// Whether commands start without the inherited environment
+    clear_env: bool,
+
+    /// The working directory of every command, `temp`, `invocation` or a path
+    cwd: Option<String>,
+
+    /// Where the files of a run are written, `temp` or `persistent`
+    workspace: Option<String>,
+
     #[serde(skip)]
     // The parsed template
     template: Template,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// Where the commands of a language are run
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkingDir {
+    /// The temporary directory of the run, holding `{{IN}}` and `{{OUT}}`
+    Temp,
+
+    /// The directory `QuickerMD` was started in
+    Invocation,
+
+    Path(PathBuf),
+}
+
+/// Where the files of a run are written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Workspace {
+    /// A new temporary directory, removed after the run
+    Temp,
+
+    /// A directory of the language in the user's cache, kept between runs
+    /// so the builds of its dependencies are reused
+    Persistent,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum RunCommandType {
     Bool(bool),
@@ -73,52 +172,954 @@ impl Default for Template {
     }
 }
 
+/// Merges `overlay` into `base` key by key, recording the source of every
+/// setting that `overlay` sets
+fn merge_table(
+    base: &mut Table,
+    overlay: Table,
+    prefix: &str,
+    source: &Path,
+    sources: &mut BTreeMap<String, PathBuf>,
+) {
+    for (key, value) in overlay {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(table)) => {
+                merge_table(base_table, table, &path, source, sources)
+            }
+            (_, value) => {
+                let nested = format!("{}.", path);
+                sources.retain(|key, _| !key.starts_with(&nested));
+                record_sources(&value, &path, source, sources);
+
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn record_sources(
+    value: &Value,
+    path: &str,
+    source: &Path,
+    sources: &mut BTreeMap<String, PathBuf>,
+) {
+    match value {
+        Value::Table(table) => {
+            for (key, value) in table {
+                record_sources(value, &format!("{}.{}", path, key), source, sources);
+            }
+        }
+        _ => {
+            sources.insert(path.to_string(), source.to_path_buf());
+        }
+    }
+}
+
+/// Settings that replace each other, so that a language setting the first
+/// drops the second it would otherwise inherit
+const REPLACED_SETTINGS: [(&str, &str); 2] =
+    [("template", "template_file"), ("template_file", "template")];
+
+/// Removes the settings of `base` that the settings of `overlay` replace
+fn drop_replaced(base: &mut Table, overlay: &Table) {
+    for (set, replaced) in REPLACED_SETTINGS {
+        if overlay.contains_key(set) && !overlay.contains_key(replaced) {
+            base.remove(replaced);
+        }
+    }
+}
+
+/// Removes the settings of the languages of `merged` that the languages of
+/// `overlay` replace, along with their sources
+fn drop_replaced_langs(
+    merged: &mut Table,
+    overlay: &Table,
+    sources: &mut BTreeMap<String, PathBuf>,
+) {
+    let (Some(Value::Table(langs)), Some(Value::Table(overlay_langs))) =
+        (merged.get_mut("langs"), overlay.get("langs"))
+    else {
+        return;
+    };
+
+    for (name, overlay_lang) in overlay_langs {
+        let (Some(Value::Table(lang)), Value::Table(overlay_lang)) =
+            (langs.get_mut(name), overlay_lang)
+        else {
+            continue;
+        };
+
+        drop_replaced(lang, overlay_lang);
+
+        for (_, replaced) in REPLACED_SETTINGS {
+            if !lang.contains_key(replaced) {
+                sources.remove(&format!("langs.{}.{}", name, replaced));
+            }
+        }
+    }
+}
+
+/// Fails with a problem with the setting `key` of `langs.name`
+fn lang_problem(
+    sources: &BTreeMap<String, PathBuf>,
+    name: &str,
+    key: &str,
+    message: String,
+) -> QuickerError {
+    let path = sources
+        .get(&format!("langs.{}.{}", name, key))
+        .cloned()
+        .unwrap_or_default();
+
+    QuickerError::InvalidConfig(vec![ConfigProblem::new(
+        &path,
+        &["langs", name, key],
+        message,
+    )])
+}
+
+/// Fills in every `[langs.*]` table with a `preset` with the settings of
+/// the preset, overridden by its own
+fn resolve_presets(
+    merged: &mut Table,
+    sources: &mut BTreeMap<String, PathBuf>,
+) -> Result<(), QuickerError> {
+    let Some(Value::Table(langs)) = merged.get_mut("langs") else {
+        return Ok(());
+    };
+
+    for (name, lang) in langs.iter_mut() {
+        // Anything else is reported when the config is deserialized
+        let Value::Table(own) = lang else {
+            continue;
+        };
+
+        let preset = match own.get("preset") {
+            None => continue,
+            Some(Value::String(preset)) => preset.clone(),
+            Some(_) => {
+                return Err(lang_problem(
+                    sources,
+                    name,
+                    "preset",
+                    format!("`preset` of `langs.{}` must be the name of a preset", name),
+                ))
+            }
+        };
+
+        let Some(mut table) = presets::get_table(&preset) else {
+            return Err(lang_problem(
+                sources,
+                name,
+                "preset",
+                format!(
+                    "`preset` of `langs.{}` is `{}`, which is not a preset, expected one of {}",
+                    name,
+                    preset,
+                    presets::names().join(", ")
+                ),
+            ));
+        };
+
+        drop_replaced(&mut table, own);
+
+        // The settings the language sets itself keep their own source
+        let mut preset_sources = BTreeMap::new();
+        record_sources(
+            &Value::Table(table.clone()),
+            &format!("langs.{}", name),
+            &presets::get_source_path(&preset),
+            &mut preset_sources,
+        );
+
+        for (key, path) in preset_sources {
+            sources.entry(key).or_insert(path);
+        }
+
+        merge_table(
+            &mut table,
+            own.clone(),
+            "",
+            Path::new(""),
+            &mut BTreeMap::new(),
+        );
+        *own = table;
+    }
+
+    Ok(())
+}
+
+/// Replaces every `[langs.*]` table that `extends` another language with
+/// its parent's settings, overridden by its own
+fn resolve_extends(
+    merged: &mut Table,
+    sources: &mut BTreeMap<String, PathBuf>,
+) -> Result<(), QuickerError> {
+    let Some(Value::Table(langs)) = merged.get_mut("langs") else {
+        return Ok(());
+    };
+
+    let mut resolved = HashMap::new();
+    let names: Vec<String> = langs.keys().cloned().collect();
+
+    for name in names.iter() {
+        resolve_lang_extends(langs, name, &mut resolved, &mut Vec::new(), sources)?;
+    }
+
+    for (name, table) in resolved {
+        langs.insert(name, Value::Table(table));
+    }
+
+    Ok(())
+}
+
+fn resolve_lang_extends(
+    langs: &Table,
+    name: &str,
+    resolved: &mut HashMap<String, Table>,
+    chain: &mut Vec<String>,
+    sources: &mut BTreeMap<String, PathBuf>,
+) -> Result<Table, QuickerError> {
+    if let Some(table) = resolved.get(name) {
+        return Ok(table.clone());
+    }
+
+    // Anything else is reported when the config is deserialized
+    let Some(Value::Table(own)) = langs.get(name) else {
+        return Ok(Table::new());
+    };
+
+    let problem = |message: String| lang_problem(sources, name, "extends", message);
+
+    let parent = match own.get("extends") {
+        None => {
+            resolved.insert(name.to_string(), own.clone());
+            return Ok(own.clone());
+        }
+        Some(Value::String(parent)) => parent.clone(),
+        Some(_) => {
+            return Err(problem(format!(
+                "`extends` of `langs.{}` must be the name of a language",
+                name
+            )))
+        }
+    };
+
+    chain.push(name.to_string());
+
+    if chain.contains(&parent) {
+        chain.push(parent);
+        return Err(problem(format!(
+            "`langs.{}` extends itself: {}",
+            name,
+            chain.join(" -> ")
+        )));
+    }
+
+    if !langs.contains_key(&parent) {
+        return Err(problem(format!(
+            "`langs.{}` extends `{}`, which does not exist",
+            name, parent
+        )));
+    }
+
+    let mut table = resolve_lang_extends(langs, &parent, resolved, chain, sources)?;
+    chain.pop();
+
+    // A language is not looked up by the names of its parent
+    table.remove("extends");
+    table.remove("aliases");
+    drop_replaced(&mut table, own);
+
+    // Inherited settings keep the file of the parent as their source
+    let parent_prefix = format!("langs.{}.", parent);
+    let mut inherited = Vec::new();
+
+    for (key, path) in sources.range(parent_prefix.clone()..) {
+        let Some(setting) = key.strip_prefix(&parent_prefix) else {
+            break;
+        };
+
+        if table.contains_key(setting.split('.').next().unwrap_or_default()) {
+            inherited.push((format!("langs.{}.{}", name, setting), path.clone()));
+        }
+    }
+
+    for (key, path) in inherited {
+        sources.entry(key).or_insert(path);
+    }
+
+    // The sources of the language's own settings are already recorded
+    let mut own_sources = BTreeMap::new();
+    merge_table(&mut table, own.clone(), "", Path::new(""), &mut own_sources);
+
+    resolved.insert(name.to_string(), table.clone());
+
+    Ok(table)
+}
+
 impl Config {
-    /// Gets the config from the default file location
-    pub fn get_config() -> Result<Self, String> {
-        let path = ProjectDirs::from("", "", "QuickMD")
-            .ok_or("Could not resolve project directory")?
+    /// Returns the JSON Schema of `config.toml`
+    ///
+    /// ```
+    /// use quickermd::user_config::Config;
+    ///
+    /// let schema = Config::schema();
+    /// assert!(schema["properties"]["langs"]["additionalProperties"]["properties"]["command"].is_object());
+    /// ```
+    pub fn schema() -> serde_json::Value {
+        let config = schema::table::<Config>(|setting| match setting {
+            "langs" => Some(serde_json::json!({
+                "type": "object",
+                "additionalProperties": LanguageConfig::schema(),
+                "description": "The languages that can be run, by name",
+            })),
+            _ => None,
+        });
+
+        schema::document("QuickerMD config", config)
+    }
+
+    /// Returns the directory the persistent workspaces are kept in, by default
+    pub fn get_workspace_root() -> Result<PathBuf, QuickerError> {
+        Ok(ProjectDirs::from("", "", "QuickMD")
+            .ok_or(QuickerError::NoConfigDir)?
+            .cache_dir()
+            .join("workspaces"))
+    }
+
+    /// Returns the directory in `root` a language with
+    /// `workspace = "persistent"` is run in
+    ///
+    /// ```
+    /// use quickermd::user_config::Config;
+    /// use std::path::Path;
+    ///
+    /// let dir = Config::get_workspace_dir(Path::new("workspaces"), "c/c++");
+    /// assert_eq!(dir, Path::new("workspaces").join("c_c++"));
+    /// ```
+    pub fn get_workspace_dir(root: &Path, lang: &str) -> PathBuf {
+        // Language names are only used as a single directory
+        let name: String = lang
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || "+-_.".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+
+        root.join(name)
+    }
+
+    /// Returns the path of the global config
+    pub fn get_global_path() -> Result<PathBuf, QuickerError> {
+        Ok(ProjectDirs::from("", "", "QuickMD")
+            .ok_or(QuickerError::NoConfigDir)?
             .config_local_dir()
-            .join("config.toml");
+            .join("config.toml"))
+    }
+
+    /// Returns the file set with `QUICKERMD_CONFIG`, if any
+    pub fn get_env_path() -> Option<PathBuf> {
+        std::env::var_os(CONFIG_ENV)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+    }
+
+    /// Returns the file the config is read from when there is no project
+    /// config: the one set with `QUICKERMD_CONFIG`, or the global config
+    pub fn get_path() -> Result<PathBuf, QuickerError> {
+        match Config::get_env_path() {
+            Some(path) => Ok(path),
+            None => Config::get_global_path(),
+        }
+    }
+
+    /// Gets the global config, with every `.quickermd.toml` from the current
+    /// directory upwards merged over it, the closest one last
+    ///
+    /// A built-in config is used when there is no global config, and
+    /// `QUICKERMD_CONFIG` replaces all of them with a single file
+    pub fn get_config() -> Result<Self, QuickerError> {
+        Config::load_unchecked()?.checked()
+    }
+
+    /// Gets the config the same way as `Config::get_config`, without
+    /// validating it
+    pub fn load_unchecked() -> Result<Self, QuickerError> {
+        if let Some(path) = Config::get_env_path() {
+            return Config::from_layers_unchecked(&[path]);
+        }
+
+        let global = Config::get_global_path()?;
+        let cwd = std::env::current_dir()?;
+
+        let mut layers = Vec::new();
+
+        if global.exists() {
+            layers.push(Config::read_layer(&global)?);
+        } else {
+            layers.push(Config::fallback_layer());
+        }
+
+        for path in Config::find_local_configs(&cwd) {
+            layers.push(Config::read_layer(&path)?);
+        }
 
-        Config::get_config_from_path(path)
+        Config::merge_layers(layers)
+    }
+
+    /// Gets the built-in config, with entries for `py` and `js`
+    ///
+    /// ```
+    /// use quickermd::user_config::Config;
+    ///
+    /// let config = Config::fallback();
+    /// assert!(config.get_lang_conf("py").is_some());
+    /// ```
+    pub fn fallback() -> Self {
+        Config::merge_layers(vec![Config::fallback_layer()]).expect("The fallback config is valid")
+    }
+
+    /// Finds every `.quickermd.toml` in `dir` and its ancestors, the
+    /// furthest one first
+    pub fn find_local_configs(dir: &Path) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = dir
+            .ancestors()
+            .map(|dir| dir.join(LOCAL_CONFIG_NAME))
+            .filter(|path| path.is_file())
+            .collect();
+        paths.reverse();
+
+        paths
     }
 
     /// Gets the config from a given path
-    pub fn get_config_from_path(path: PathBuf) -> Result<Self, String> {
-        let config_contents = std::fs::read_to_string(path.clone()).map_err(|e| {
-            format!(
-                "Error reading file '{}':\n{}",
-                path.to_str().unwrap_or_default(),
-                e.to_string()
-            )
-        })?;
+    pub fn get_config_from_path(path: PathBuf) -> Result<Self, QuickerError> {
+        Config::from_layers(&[path])
+    }
+
+    /// Gets the config by merging `paths` in order, each file overriding
+    /// the settings of the files before it
+    pub fn from_layers(paths: &[PathBuf]) -> Result<Self, QuickerError> {
+        Config::from_layers_unchecked(paths)?.checked()
+    }
+
+    /// Merges `paths` the same way as `Config::from_layers`, without
+    /// validating the result
+    pub fn from_layers_unchecked(paths: &[PathBuf]) -> Result<Self, QuickerError> {
+        let layers = paths
+            .iter()
+            .map(|path| Config::read_layer(path))
+            .collect::<Result<Vec<_>, QuickerError>>()?;
+
+        Config::merge_layers(layers)
+    }
 
-        let config = toml::from_str::<Config>(&config_contents).map_err(|e| {
-            format!(
-                "There was an error reading the config '{}':\n{}",
-                path.to_str().unwrap_or_default(),
-                e.to_string()
-            )
+    /// Reads a single config file, without checking that it is complete
+    fn read_layer(path: &Path) -> Result<(PathBuf, Table), QuickerError> {
+        let config_contents =
+            std::fs::read_to_string(path).map_err(|source| match source.kind() {
+                std::io::ErrorKind::NotFound => QuickerError::ConfigNotFound(path.to_path_buf()),
+                _ => QuickerError::ReadFile {
+                    path: path.to_path_buf(),
+                    source,
+                },
+            })?;
+
+        let table = toml::from_str::<Table>(&config_contents).map_err(|source| {
+            QuickerError::ConfigParse {
+                paths: vec![path.to_path_buf()],
+                source,
+            }
         })?;
 
+        Ok((path.to_path_buf(), table))
+    }
+
+    fn fallback_layer() -> (PathBuf, Table) {
+        let table = toml::from_str::<Table>(FALLBACK_CONFIG).expect("The fallback config is valid");
+
+        (PathBuf::from(FALLBACK_SOURCE), table)
+    }
+
+    fn merge_layers(layers: Vec<(PathBuf, Table)>) -> Result<Self, QuickerError> {
+        Config::merge_layers_with_env(layers, |name| std::env::var(name).ok())
+    }
+
+    /// Merges `layers` the same way as `Config::merge_layers`, with `lookup`
+    /// finding the value of each environment variable
+    fn merge_layers_with_env(
+        layers: Vec<(PathBuf, Table)>,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, QuickerError> {
+        let mut merged = Table::new();
+        let mut sources = BTreeMap::new();
+        let paths: Vec<PathBuf> = layers.iter().map(|(path, _)| path.clone()).collect();
+
+        for (path, table) in layers {
+            drop_replaced_langs(&mut merged, &table, &mut sources);
+            merge_table(&mut merged, table, "", &path, &mut sources);
+        }
+
+        resolve_presets(&mut merged, &mut sources)?;
+        resolve_extends(&mut merged, &mut sources)?;
+
+        let mut config = Value::Table(merged)
+            .try_into::<Config>()
+            .map_err(|source| QuickerError::ConfigParse { paths, source })?;
+
+        config.sources = sources;
+        config.expand_env(lookup)?;
+        config.resolve_paths();
+        config.load_template_files()?;
+
         Ok(config)
     }
 
-    // Gets the associated config for the `lang`
+    /// Expands the environment variables in the `command`, `run`, `template`,
+    /// `template_file`, `extension`, `comment`, `cwd`, `env` and
+    /// `path_prepend` of every language, failing with every variable that
+    /// is not set
+    fn expand_env(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<(), QuickerError> {
+        let mut failures: Vec<(Vec<String>, String)> = Vec::new();
+
+        let mut names: Vec<String> = self.langs.keys().cloned().collect();
+        names.sort();
+
+        for name in names.iter() {
+            let lang = self.langs.get_mut(name).expect("The name is a key");
+
+            let mut expand =
+                |value: &mut String, key: Vec<String>| match env::expand(value, &lookup) {
+                    Ok(expanded) => *value = expanded,
+                    Err(e) => {
+                        let message = format!("`{}` of `langs.{}` {}", key[0], name, e);
+                        let key = ["langs".to_string(), name.clone()].into_iter().chain(key);
+                        failures.push((key.collect(), message));
+                    }
+                };
+
+            for (idx, arg) in lang.compile_command.iter_mut().enumerate() {
+                expand(arg, vec!["command".to_string(), idx.to_string()]);
+            }
+
+            if let Some(RunCommandType::StringVec(run)) = &mut lang.run_command {
+                for (idx, arg) in run.iter_mut().enumerate() {
+                    expand(arg, vec!["run".to_string(), idx.to_string()]);
+                }
+            }
+
+            for (key, value) in [
+                ("template", &mut lang.raw_template),
+                ("extension", &mut lang.extension),
+                ("filename", &mut lang.filename),
+                ("output_name", &mut lang.output_name),
+                ("comment", &mut lang.comment),
+                ("cwd", &mut lang.cwd),
+                ("template_file", &mut lang.template_file),
+            ] {
+                if let Some(value) = value {
+                    expand(value, vec![key.to_string()]);
+                }
+            }
+
+            for (file, contents) in lang.files.iter_mut() {
+                expand(contents, vec!["files".to_string(), file.clone()]);
+            }
+
+            for (key, value) in lang.env.iter_mut() {
+                expand(value, vec!["env".to_string(), key.clone()]);
+            }
+
+            for (idx, dir) in lang.path_prepend.iter_mut().enumerate() {
+                expand(dir, vec!["path_prepend".to_string(), idx.to_string()]);
+            }
+        }
+
+        if failures.is_empty() {
+            return Ok(());
+        }
+
+        let problems = failures
+            .into_iter()
+            .map(|(key, message)| {
+                let key: Vec<&str> = key.iter().map(|segment| segment.as_str()).collect();
+                self.problem(&key, message)
+            })
+            .collect();
+
+        Err(QuickerError::InvalidConfig(problems))
+    }
+
+    /// Makes the relative paths of every language relative to the directory
+    /// of the config file that set them
+    fn resolve_paths(&mut self) {
+        for (name, lang) in self.langs.iter_mut() {
+            let source_dir = |key: &str| {
+                self.sources
+                    .get(&format!("langs.{}.{}", name, key))
+                    .and_then(|source| source.parent())
+                    .map(|dir| dir.to_path_buf())
+                    .unwrap_or_default()
+            };
+
+            if let WorkingDir::Path(path) = lang.get_cwd() {
+                lang.cwd = Some(source_dir("cwd").join(path).to_string_lossy().to_string());
+            }
+
+            let dir = source_dir("path_prepend");
+            for path in lang.path_prepend.iter_mut() {
+                *path = dir.join(&path).to_string_lossy().to_string();
+            }
+
+            if let Some(file) = &lang.template_file {
+                lang.template_file = Some(
+                    source_dir("template_file")
+                        .join(file)
+                        .to_string_lossy()
+                        .to_string(),
+                );
+            }
+        }
+    }
+
+    /// Reads the `template_file` of every language into its template,
+    /// failing with every file that can not be read
+    fn load_template_files(&mut self) -> Result<(), QuickerError> {
+        let mut failures = Vec::new();
+
+        for (name, lang) in self.langs.iter_mut() {
+            let Some(file) = &lang.template_file else {
+                continue;
+            };
+
+            if lang.raw_template.is_some() {
+                failures.push((
+                    name.clone(),
+                    format!(
+                        "`langs.{}` sets both `template` and `template_file`, only one can be used",
+                        name
+                    ),
+                ));
+                continue;
+            }
+
+            match std::fs::read_to_string(file) {
+                Ok(template) => lang.raw_template = Some(template),
+                Err(e) => failures.push((
+                    name.clone(),
+                    format!(
+                        "`template_file` of `langs.{}` is `{}`, which could not be read: {}",
+                        name, file, e
+                    ),
+                )),
+            }
+        }
+
+        if failures.is_empty() {
+            return Ok(());
+        }
+
+        failures.sort();
+
+        let problems = failures
+            .into_iter()
+            .map(|(name, message)| self.problem(&["langs", &name, "template_file"], message))
+            .collect();
+
+        Err(QuickerError::InvalidConfig(problems))
+    }
+
+    /// Fails with every problem `Config::validate` finds
+    fn checked(self) -> Result<Self, QuickerError> {
+        let problems = self.validate();
+
+        if problems.is_empty() {
+            return Ok(self);
+        }
+
+        Err(QuickerError::InvalidConfig(problems))
+    }
+
+    /// Finds the settings that would fail or be ignored when running,
+    /// in the order of the languages' names
+    ///
+    /// Settings a language inherits with `extends` are only reported on its parent
+    pub fn validate(&self) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+        let mut names: Vec<&String> = self.langs.keys().collect();
+        names.sort();
+
+        for name in names.iter().copied() {
+            let lang = &self.langs[name];
+            let parent = lang
+                .extends
+                .as_ref()
+                .and_then(|parent| self.langs.get(parent));
+            let owns = |same: &dyn Fn(&LanguageConfig) -> bool| parent.is_none_or(|p| !same(p));
+            let mut report = |key: &[&str], message: String| {
+                let key: Vec<&str> = ["langs", name].iter().chain(key).copied().collect();
+                problems.push(self.problem(&key, message));
+            };
+
+            let has_program = |command: &Vec<String>| {
+                command
+                    .first()
+                    .is_some_and(|program| !program.trim().is_empty())
+            };
+
+            if !has_program(&lang.compile_command)
+                && owns(&|p| p.compile_command == lang.compile_command)
+            {
+                report(
+                    &["command"],
+                    format!(
+                        "`command` of `langs.{}` must start with the program to run",
+                        name
+                    ),
+                );
+            }
+
+            if let Some(RunCommandType::StringVec(run)) = &lang.run_command {
+                if !has_program(run) && owns(&|p| p.run_command == lang.run_command) {
+                    report(
+                        &["run"],
+                        format!(
+                            "`run` of `langs.{}` must be `true`, `false` or a command",
+                            name
+                        ),
+                    );
+                }
+            }
+
+            // The input can also be written to one of the `files`
+            let files_take_input = lang
+                .files
+                .values()
+                .any(|contents| contents.contains("{{INPUT}}"));
+
+            if let Some(template) = &lang.raw_template {
+                if !template.contains("{{INPUT}}")
+                    && !files_take_input
+                    && owns(&|p| p.raw_template == lang.raw_template)
+                {
+                    let key = match lang.template_file {
+                        Some(_) => "template_file",
+                        None => "template",
+                    };
+
+                    report(
+                        &[key],
+                        format!(
+                            "`{}` of `langs.{}` has no `{{{{INPUT}}}}`, so the input would be dropped",
+                            key, name
+                        ),
+                    );
+                }
+            }
+
+            if let Some(timeout) = lang.timeout {
+                let valid = timeout > 0.0 && Duration::try_from_secs_f64(timeout).is_ok();
+
+                if !valid && owns(&|p| p.timeout == lang.timeout) {
+                    report(
+                        &["timeout"],
+                        format!(
+                            "`timeout` of `langs.{}` must be a positive number of seconds",
+                            name
+                        ),
+                    );
+                }
+            }
+
+            if lang.comment.as_deref() == Some("") && owns(&|p| p.comment == lang.comment) {
+                report(
+                    &["comment"],
+                    format!(
+                        "`comment` of `langs.{}` is empty, leave it out to write output without a comment",
+                        name
+                    ),
+                );
+            }
+
+            for file in lang.files.keys() {
+                if !utils::is_inside_dir(file) && owns(&|p| p.files.contains_key(file)) {
+                    report(
+                        &["files", file],
+                        format!(
+                            "`{}` in `files` of `langs.{}` must be a relative path inside the run's directory",
+                            file, name
+                        ),
+                    );
+                }
+            }
+
+            for (key, value) in [
+                ("filename", &lang.filename),
+                ("output_name", &lang.output_name),
+            ] {
+                let Some(value) = value else {
+                    continue;
+                };
+
+                let owned = owns(&|p| match key {
+                    "filename" => p.filename == lang.filename,
+                    _ => p.output_name == lang.output_name,
+                });
+
+                if !utils::is_inside_dir(value) && owned {
+                    report(
+                        &[key],
+                        format!(
+                            "`{}` of `langs.{}` is `{}`, which must be a relative path inside the run's directory",
+                            key, name, value
+                        ),
+                    );
+                }
+            }
+
+            if let Some(workspace) = &lang.workspace {
+                if !["temp", "persistent"].contains(&workspace.as_str())
+                    && owns(&|p| p.workspace == lang.workspace)
+                {
+                    report(
+                        &["workspace"],
+                        format!(
+                            "`workspace` of `langs.{}` is `{}`, expected `temp` or `persistent`",
+                            name, workspace
+                        ),
+                    );
+                }
+            }
+
+            if let WorkingDir::Path(path) = lang.get_cwd() {
+                if !path.is_dir() && owns(&|p| p.cwd == lang.cwd) {
+                    report(
+                        &["cwd"],
+                        format!(
+                            "`cwd` of `langs.{}` is `{}`, which is not a directory",
+                            name,
+                            path.to_str().unwrap_or_default()
+                        ),
+                    );
+                }
+            }
+
+            for (idx, alias) in lang.aliases.iter().enumerate() {
+                let idx = idx.to_string();
+
+                let owner = names.iter().copied().find(|other| {
+                    *other != name
+                        && (*other == alias || self.langs[*other].aliases.contains(alias))
+                });
+
+                match owner {
+                    Some(other) if other == alias => report(
+                        &["aliases", &idx],
+                        format!(
+                            "alias `{}` of `langs.{}` is the name of another language",
+                            alias, name
+                        ),
+                    ),
+                    Some(other) => report(
+                        &["aliases", &idx],
+                        format!(
+                            "alias `{}` of `langs.{}` is also an alias of `langs.{}`",
+                            alias, name, other
+                        ),
+                    ),
+                    None => {}
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// Creates a problem with the setting at `key`, in the file it came from
+    fn problem(&self, key: &[&str], message: String) -> ConfigProblem {
+        let table = format!("{}.", key[..key.len() - 1].join("."));
+
+        // Array elements are found by the source of their array
+        let path = (1..=key.len())
+            .rev()
+            .find_map(|len| self.sources.get(&key[..len].join(".")))
+            .or_else(|| {
+                self.sources
+                    .iter()
+                    .find(|(source_key, _)| source_key.starts_with(&table))
+                    .map(|(_, path)| path)
+            })
+            .cloned()
+            .unwrap_or_default();
+
+        ConfigProblem::new(&path, key, message)
+    }
+
+    /// Returns the file each setting came from, by its dotted key,
+    /// i.e. `langs.c.command`
+    pub fn get_sources(&self) -> &BTreeMap<String, PathBuf> {
+        &self.sources
+    }
+
+    /// Returns the name of the language `name` refers to, matching the
+    /// names first, then the `aliases`, then the `extension` of each language
+    ///
+    /// ```
+    /// use quickermd::user_config::Config;
+    /// use std::path::PathBuf;
+    /// # fn main() -> Result<(), quickermd::QuickerError> {
+    /// let config = Config::get_config_from_path(PathBuf::from("../examples/config.toml"))?;
+    ///
+    /// assert_eq!(config.resolve_lang("javascript"), Some("js"));
+    /// assert_eq!(config.resolve_lang("rs"), Some("rust"));
+    /// assert_eq!(config.resolve_lang("main.rs"), None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resolve_lang(&self, name: &str) -> Option<&str> {
+        if let Some((key, _)) = self.langs.get_key_value(name) {
+            return Some(key);
+        }
+
+        // Sorted, so that a name shared by several languages always resolves the same way
+        let mut keys: Vec<&String> = self.langs.keys().collect();
+        keys.sort();
+
+        keys.iter()
+            .find(|key| self.langs[**key].aliases.iter().any(|alias| alias == name))
+            .or_else(|| {
+                keys.iter()
+                    .find(|key| self.langs[**key].extension.as_deref() == Some(name))
+            })
+            .map(|key| key.as_str())
+    }
+
+    // Gets the associated config for the `lang`, or one of its aliases
     pub fn get_lang_conf(&self, lang: &str) -> Option<&LanguageConfig> {
-        self.langs.get(lang)
+        self.langs.get(self.resolve_lang(lang)?)
     }
 
-    // Gets the associated config for the `lang`
+    // Gets the associated config for the `lang`, or one of its aliases
     pub fn get_mut_lang_conf(&mut self, lang: &str) -> Option<&mut LanguageConfig> {
-        self.langs.get_mut(lang)
+        let key = self.resolve_lang(lang)?.to_string();
+        self.langs.get_mut(&key)
     }
 }
 
 impl LanguageConfig {
     /// Gets the command name for the `LanguageConfig`
-    /// 
+    ///
     /// ## Example
     /// ```toml
     /// [langs.c]
@@ -128,8 +1129,8 @@ impl LanguageConfig {
     /// ```
     /// use quickermd::QuickerMD;
     /// use std::path::PathBuf;
-    /// 
-    /// # fn main() -> Result<(), String> {
+    ///
+    /// # fn main() -> Result<(), quickermd::QuickerError> {
     /// let config = QuickerMD::from_file_path(PathBuf::from("../examples/config.toml"))?;
     /// let c_config = config.get_config_for_lang("c")?;
     /// assert_eq!(c_config.get_command_name(), "gcc");
@@ -137,11 +1138,11 @@ impl LanguageConfig {
     /// # }
     /// ```
     pub fn get_command_name(&self) -> String {
-        self.compile_command[0].clone()
+        self.compile_command.first().cloned().unwrap_or_default()
     }
 
     /// Gets the command arguments for the `LanguageConfig`
-    /// 
+    ///
     /// ## Example
     /// ```toml
     /// [langs.c]
@@ -150,8 +1151,8 @@ impl LanguageConfig {
     /// ```
     /// use quickermd::QuickerMD;
     /// use std::path::PathBuf;
-    /// 
-    /// # fn main() -> Result<(), String> {
+    ///
+    /// # fn main() -> Result<(), quickermd::QuickerError> {
     /// let config = QuickerMD::from_file_path(PathBuf::from("../examples/config.toml"))?;
     /// let c_config = config.get_config_for_lang("c")?;
     /// assert_eq!(c_config.get_command_args(), vec![
@@ -178,10 +1179,9 @@ impl LanguageConfig {
                         None
                     }
                 }
-                RunCommandType::StringVec(command) => Some((
-                    command[0].clone(),
-                    command.iter().take(1).cloned().collect(),
-                )),
+                RunCommandType::StringVec(command) => command
+                    .split_first()
+                    .map(|(program, args)| (program.clone(), args.to_vec())),
             };
         }
 
@@ -202,7 +1202,8 @@ impl LanguageConfig {
     pub fn get_comment_string(&self) -> Option<String> {
         if let Some(comment) = self.comment.clone() {
             if !comment.contains("%s") {
-                if comment.chars().last().unwrap().is_whitespace() {
+                // An empty comment leaves the lines as they are
+                if comment.chars().last().is_none_or(char::is_whitespace) {
                     return Some(format!("{}%s", comment));
                 }
                 return Some(format!("{} %s", comment));
@@ -222,38 +1223,227 @@ impl LanguageConfig {
         None
     }
 
+    /// Gets the prefix of hidden lines
+    pub fn get_hidden_prefix(&self) -> Option<String> {
+        self.hidden.clone()
+    }
+
+    /// Strips the hidden prefix from `lines`, so that they can be run
+    ///
+    /// ```
+    /// use quickermd::QuickerMD;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> Result<(), quickermd::QuickerError> {
+    /// let config = QuickerMD::from_file_path(PathBuf::from("../examples/config.toml"))?;
+    /// let rust_config = config.get_config_for_lang("rust")?;
+    /// let lines = vec!["# use std::fmt;".to_string(), "#".to_string(), "main()".to_string()];
+    ///
+    /// assert_eq!(rust_config.get_runnable_lines(lines), vec!["use std::fmt;", "", "main()"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_runnable_lines(&self, lines: Vec<String>) -> Vec<String> {
+        let Some(prefix) = &self.hidden else {
+            return lines;
+        };
+
+        lines
+            .into_iter()
+            .map(|line| match line.strip_prefix(prefix.as_str()) {
+                Some(stripped) => stripped.to_string(),
+                None if line == prefix.trim_end() => String::new(),
+                None => line,
+            })
+            .collect()
+    }
+
+    /// Removes the hidden lines from `input`, so that it can be displayed
+    pub fn get_visible_input(&self, input: &str) -> String {
+        let Some(prefix) = &self.hidden else {
+            return input.to_string();
+        };
+
+        input
+            .lines()
+            .filter(|line| !line.starts_with(prefix.as_str()) && *line != prefix.trim_end())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
     pub fn get_redir_input(&self) -> bool {
         self.redir_input
     }
 
+    /// Gets the time limit of each command
+    pub fn get_timeout(&self) -> Option<Duration> {
+        self.timeout
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+    }
+
+    /// Gets the environment variables set for every command
+    pub fn get_env(&self) -> &BTreeMap<String, String> {
+        &self.env
+    }
+
+    /// Gets the directories searched for programs before `PATH`
+    pub fn get_path_prepend(&self) -> &Vec<String> {
+        &self.path_prepend
+    }
+
+    /// Whether commands start without the inherited environment
+    pub fn clears_env(&self) -> bool {
+        self.clear_env
+    }
+
+    /// Gets the working directory of every command, the invocation
+    /// directory by default
+    pub fn get_cwd(&self) -> WorkingDir {
+        match self.cwd.as_deref() {
+            None | Some("invocation") => WorkingDir::Invocation,
+            Some("temp") => WorkingDir::Temp,
+            Some(path) => WorkingDir::Path(PathBuf::from(path)),
+        }
+    }
+
+    pub fn get_workspace(&self) -> Workspace {
+        match self.workspace.as_deref() {
+            Some("persistent") => Workspace::Persistent,
+            _ => Workspace::Temp,
+        }
+    }
+
+    /// Returns the JSON Schema of a `[langs.*]` table
+    pub fn schema() -> serde_json::Value {
+        schema::table::<LanguageConfig>(|setting| {
+            Some(match setting {
+                "template" => schema::string("The template the input is written into, at `{{INPUT}}`"),
+                "template_file" => schema::string(
+                    "The file the template is read from, relative to this config, in place of `template`",
+                ),
+                "files" => schema::string_table(
+                    "Other files written to the directory of the run, by their relative path",
+                ),
+                "comment" => schema::string("The comment prefix the output is written with, i.e. `// `"),
+                "hidden" => schema::string("The prefix of lines that are run, but never displayed"),
+                "extension" => schema::string("The extension of the file the template is written to"),
+                "filename" => schema::string(
+                    "The name of the file the template is written to, in place of `tmp.<extension>`",
+                ),
+                "output_name" => schema::string("The name of the file `{{OUT}}` refers to, in place of `out`"),
+                "aliases" => schema::strings("Other names the language can be looked up by"),
+                "extends" => schema::string("The language this language inherits its settings from"),
+                "preset" => schema::one_of(
+                    &presets::names(),
+                    "The built-in language definition this language starts from",
+                ),
+                "command" => schema::strings("The command that is run first, with its arguments"),
+                "run" => serde_json::json!({
+                    "anyOf": [
+                        { "type": "boolean" },
+                        { "type": "array", "items": { "type": "string" } },
+                    ],
+                    "description": "The command that runs `{{OUT}}` after `command`, or `false` to only run `command`",
+                }),
+                "redir_input" => schema::boolean("Whether the input is written to the stdin of `command`"),
+                "timeout" => serde_json::json!({
+                    "type": "number",
+                    "exclusiveMinimum": 0,
+                    "description": "The time limit of each command, in seconds",
+                }),
+                "env" => schema::string_table("Environment variables set for every command"),
+                "path_prepend" => schema::strings(
+                    "Directories searched for programs before the ones on `PATH`, relative to this config",
+                ),
+                "clear_env" => schema::boolean("Whether commands start without the inherited environment"),
+                "cwd" => schema::string(
+                    "The working directory of every command, `temp`, `invocation` or a path relative to this config",
+                ),
+                "workspace" => schema::one_of(
+                    &["temp", "persistent"],
+                    "Where the files of a run are written, `persistent` keeps them between runs",
+                ),
+                _ => return None,
+            })
+        })
+    }
+
+    /// Gets the config of a preset, as if it was a language of its own
+    ///
+    /// ```
+    /// use quickermd::user_config::LanguageConfig;
+    ///
+    /// let rust = LanguageConfig::from_preset("rust").unwrap();
+    /// assert_eq!(rust.get_extension(), Some("rs".to_string()));
+    /// ```
+    pub fn from_preset(name: &str) -> Option<Self> {
+        let table = presets::get_table(name)?;
+
+        Some(
+            Value::Table(table)
+                .try_into::<LanguageConfig>()
+                .expect("The presets are valid"),
+        )
+    }
+
+    /// Gets the other names of the language
+    pub fn get_aliases(&self) -> &Vec<String> {
+        &self.aliases
+    }
+
+    /// Gets the language this language inherits its settings from
+    pub fn get_extends(&self) -> Option<String> {
+        self.extends.clone()
+    }
+
+    /// Gets the preset this language starts from
+    pub fn get_preset(&self) -> Option<String> {
+        self.preset.clone()
+    }
+
     pub fn get_extension(&self) -> Option<String> {
         self.extension.clone()
     }
 
+    /// Gets the name of the file the template is written to, if it is not
+    /// `tmp.<extension>`
+    pub fn get_filename(&self) -> Option<String> {
+        self.filename.clone()
+    }
+
+    /// Gets the name of the file `{{OUT}}` refers to, if it is not `out`
+    pub fn get_output_name(&self) -> Option<String> {
+        self.output_name.clone()
+    }
+
     pub fn get_raw_template(&self) -> Option<String> {
         self.raw_template.clone()
     }
 
+    /// Gets the file the template was read from, if it was not set with `template`
+    pub fn get_template_file(&self) -> Option<PathBuf> {
+        self.template_file.as_ref().map(PathBuf::from)
+    }
+
+    /// Gets the files written next to the template, by their relative path
+    pub fn get_files(&self) -> &BTreeMap<String, String> {
+        &self.files
+    }
+
     pub fn get_template(&self) -> Option<&Template> {
-        if let Some(_) = self.raw_template {
+        if self.raw_template.is_some() {
             return Some(&self.template);
         }
         None
     }
 
     pub fn explicit_no_run(&self) -> bool {
-        match &self.run_command {
-            Some(command_type) => match command_type {
-                RunCommandType::Bool(val) => !val,
-                _ => false,
-            },
-            None => false,
-        }
+        matches!(&self.run_command, Some(RunCommandType::Bool(false)))
     }
 }
 
 impl Template {
-    pub fn new(lang: &str, input: Vec<String>, lang_conf: &LanguageConfig) -> Self {
+    pub fn new(_lang: &str, input: Vec<String>, lang_conf: &LanguageConfig) -> Self {
         let input_len = input.len();
 
         let mut template = Template {
@@ -295,7 +1485,7 @@ impl Template {
 
     fn resolve(&mut self) {
         self.resolved_template = self.resolve_no_mut();
-        self.is_resolved = self.lines.len() != 0;
+        self.is_resolved = !self.lines.is_empty();
     }
 
     pub fn get_template_lines(&self) -> Vec<String> {
@@ -318,9 +1508,9 @@ impl Template {
     }
 }
 
-impl ToString for Template {
-    fn to_string(&self) -> String {
-        self.get_resolved_template()
+impl std::fmt::Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.get_resolved_template())
     }
 }
 
@@ -336,6 +1526,347 @@ mod test {
         _ = get_exmaple_config();
     }
 
+    #[test]
+    fn it_merges_layers_key_by_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let global = dir.path().join("config.toml");
+        let project = dir.path().join("project");
+        let local = project.join(LOCAL_CONFIG_NAME);
+
+        std::fs::create_dir_all(project.join("docs")).unwrap();
+        std::fs::write(
+            &global,
+            "[langs.c]\ncommand = [\"gcc\", \"{{IN}}\"]\ncomment = \"// \"\n[langs.js]\ncommand = [\"node\"]\n",
+        )
+        .unwrap();
+        std::fs::write(&local, "[langs.c]\ncommand = [\"clang\", \"{{IN}}\"]\n").unwrap();
+
+        let locals = Config::find_local_configs(&project.join("docs"));
+        assert_eq!(locals, vec![local.clone()]);
+
+        let mut layers = vec![global.clone()];
+        layers.extend(locals);
+        let config = Config::from_layers(&layers).unwrap();
+
+        let c_conf = config.get_lang_conf("c").unwrap();
+        assert_eq!(c_conf.get_command_name(), "clang");
+        assert_eq!(c_conf.get_raw_comment_string(), Some("// ".to_string()));
+        assert!(config.get_lang_conf("js").is_some());
+
+        let sources = config.get_sources();
+        assert_eq!(sources["langs.c.command"], local);
+        assert_eq!(sources["langs.c.comment"], global);
+        assert_eq!(sources["langs.js.command"], global);
+    }
+
+    #[test]
+    fn it_merges_project_configs_over_the_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = dir.path().join(LOCAL_CONFIG_NAME);
+        std::fs::write(&local, "[langs.py]\ncommand = [\"python3.12\"]\n").unwrap();
+
+        // The fallback uses the presets, which run `python3` like `init`
+        let fallback = Config::fallback();
+        assert_eq!(
+            fallback.get_lang_conf("py").unwrap().get_command_name(),
+            "python3"
+        );
+
+        let config = Config::merge_layers(vec![
+            Config::fallback_layer(),
+            Config::read_layer(&local).unwrap(),
+        ])
+        .unwrap();
+
+        let py_conf = config.get_lang_conf("py").unwrap();
+        assert_eq!(py_conf.get_command_name(), "python3.12");
+        assert!(py_conf.get_redir_input());
+        assert_eq!(
+            config.get_sources()["langs.py.redir_input"],
+            presets::get_source_path("py")
+        );
+        assert_eq!(
+            config.get_sources()["langs.py.preset"],
+            PathBuf::from(FALLBACK_SOURCE)
+        );
+    }
+
+    #[test]
+    fn it_resolves_aliases_before_extensions() {
+        let config = get_exmaple_config();
+
+        assert_eq!(config.resolve_lang("py"), Some("py"));
+        assert_eq!(config.resolve_lang("python3"), Some("py"));
+        assert_eq!(config.resolve_lang("rs"), Some("rust"));
+        assert_eq!(config.resolve_lang("ruby"), None);
+        assert_eq!(
+            config.get_lang_conf("node").unwrap().get_aliases(),
+            &vec!["javascript".to_string(), "node".to_string()]
+        );
+    }
+
+    fn config_from_str(contents: &str) -> Result<Config, QuickerError> {
+        let table = toml::from_str::<Table>(contents).unwrap();
+        Config::merge_layers(vec![(PathBuf::from("config.toml"), table)])
+    }
+
+    /// Loads `contents` with `vars` in place of the environment
+    fn config_from_str_with_env(
+        contents: &str,
+        vars: &[(&str, &str)],
+    ) -> Result<Config, QuickerError> {
+        let table = toml::from_str::<Table>(contents).unwrap();
+        Config::merge_layers_with_env(vec![(PathBuf::from("config.toml"), table)], |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn it_describes_every_setting_of_the_presets_in_the_schema() {
+        let schema = Config::schema();
+        let settings = &schema["properties"]["langs"]["additionalProperties"]["properties"];
+
+        for name in presets::names() {
+            for setting in presets::get_table(name).unwrap().keys() {
+                assert!(
+                    settings[setting].is_object(),
+                    "`{}` of the preset `{}`",
+                    setting,
+                    name
+                );
+            }
+
+            assert!(settings["preset"]["enum"]
+                .as_array()
+                .unwrap()
+                .contains(&serde_json::json!(name)));
+        }
+    }
+
+    #[test]
+    fn it_starts_a_language_from_a_preset() {
+        let config = config_from_str(
+            r#"
+[langs.rust]
+preset = "rust"
+command = ["rustc", "--edition", "2021", "{{IN}}", "-o", "{{OUT}}"]
+
+[langs.rust-debug]
+extends = "rust"
+timeout = 10
+"#,
+        )
+        .unwrap();
+
+        let rust = config.get_lang_conf("rust").unwrap();
+        assert_eq!(rust.get_command_args()[1], "2021");
+        assert_eq!(rust.get_extension(), Some("rs".to_string()));
+        assert!(rust.get_raw_template().unwrap().contains("fn main()"));
+
+        let debug = config.get_lang_conf("rust-debug").unwrap();
+        assert_eq!(debug.get_extension(), Some("rs".to_string()));
+
+        let sources = config.get_sources();
+        assert_eq!(
+            sources["langs.rust.extension"],
+            PathBuf::from("<preset:rust>")
+        );
+        assert_eq!(sources["langs.rust.command"], PathBuf::from("config.toml"));
+        assert_eq!(
+            sources["langs.rust-debug.extension"],
+            PathBuf::from("<preset:rust>")
+        );
+
+        let missing = config_from_str("[langs.x]\npreset = \"cobol\"\n");
+        assert!(missing
+            .unwrap_err()
+            .to_string()
+            .contains("`preset` of `langs.x` is `cobol`, which is not a preset"));
+    }
+
+    #[test]
+    fn it_inherits_settings_with_extends() {
+        let config = config_from_str(
+            r#"
+[langs.c-asan]
+extends = "c-debug"
+command = ["gcc", "-g", "-fsanitize=address", "{{IN}}", "-o", "{{OUT}}"]
+
+[langs.c-debug]
+extends = "c"
+aliases = ["cdbg"]
+command = ["gcc", "-g", "{{IN}}", "-o", "{{OUT}}"]
+
+[langs.c]
+aliases = ["clang"]
+command = ["gcc", "{{IN}}", "-o", "{{OUT}}"]
+comment = "// "
+template = "int main() { {{INPUT}} }"
+"#,
+        )
+        .unwrap();
+
+        let asan = config.get_lang_conf("c-asan").unwrap();
+        assert_eq!(asan.get_command_args()[1], "-fsanitize=address");
+        assert_eq!(asan.get_comment_string(), Some("// %s".to_string()));
+        assert_eq!(
+            asan.get_raw_template(),
+            config.get_lang_conf("c").unwrap().get_raw_template()
+        );
+        assert_eq!(asan.get_extends(), Some("c-debug".to_string()));
+        assert!(asan.get_aliases().is_empty());
+        assert_eq!(config.resolve_lang("clang"), Some("c"));
+
+        assert_eq!(
+            config.get_sources()["langs.c-asan.comment"],
+            PathBuf::from("config.toml")
+        );
+    }
+
+    #[test]
+    fn it_rejects_missing_and_cyclic_parents() {
+        let missing = config_from_str("[langs.a]\nextends = \"b\"\ncommand = [\"a\"]\n");
+        assert_eq!(
+            missing.unwrap_err().to_string(),
+            "There are problems with the config:\nconfig.toml: `langs.a` extends `b`, which does not exist"
+        );
+
+        let cyclic = config_from_str(
+            "[langs.a]\nextends = \"b\"\n[langs.b]\nextends = \"c\"\n[langs.c]\nextends = \"a\"\n",
+        );
+        assert!(cyclic.unwrap_err().to_string().contains("a -> b -> c -> a"));
+    }
+
+    #[test]
+    fn it_reports_problems_with_their_location() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[langs.c]\ncommand = []\ntemplate = \"int main() {}\"\n\n[langs.c-debug]\nextends = \"c\"\nrun = []\ncomment = \"\"\n",
+        )
+        .unwrap();
+
+        assert!(Config::from_layers(std::slice::from_ref(&path)).is_err());
+
+        let config = Config::from_layers_unchecked(std::slice::from_ref(&path)).unwrap();
+        let problems: Vec<(Option<(usize, usize)>, String)> = config
+            .validate()
+            .iter()
+            .map(|problem| {
+                let setting = problem.get_message().split(' ').next().unwrap();
+                (problem.get_location(), setting.to_string())
+            })
+            .collect();
+
+        // The inherited `command` and `template` are only reported on `c`
+        assert_eq!(
+            problems,
+            vec![
+                (Some((2, 11)), "`command`".to_string()),
+                (Some((3, 12)), "`template`".to_string()),
+                (Some((7, 7)), "`run`".to_string()),
+                (Some((8, 11)), "`comment`".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_expands_environment_variables() {
+        let config = config_from_str_with_env(
+            r#"
+[langs.c]
+command = ["${CC:-gcc}", "{{IN}}", "-o", "{{OUT}}"]
+run = ["${RUNNER:-env}", "{{OUT}}"]
+template = "int main() { {{INPUT}} } // $${literal}"
+"#,
+            &[("CC", "clang")],
+        )
+        .unwrap();
+
+        let c = config.get_lang_conf("c").unwrap();
+        assert_eq!(c.get_command_name(), "clang");
+        assert_eq!(
+            c.get_run_command("out".to_string()),
+            Some(("env".to_string(), vec!["{{OUT}}".to_string()]))
+        );
+        assert!(c.get_raw_template().unwrap().ends_with("// ${literal}"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[langs.c]\ncommand = [\"gcc\", \"${QUICKERMD_TEST_UNSET}\"]\n",
+        )
+        .unwrap();
+
+        match Config::get_config_from_path(path) {
+            Err(QuickerError::InvalidConfig(problems)) => {
+                assert_eq!(problems[0].get_location(), Some((2, 19)));
+                assert_eq!(
+                    problems[0].get_message(),
+                    "`command` of `langs.c` uses `${QUICKERMD_TEST_UNSET}`, which is not set and has no default"
+                );
+            }
+            _ => panic!("The unset variable is not reported"),
+        }
+    }
+
+    #[test]
+    fn it_reads_templates_from_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        std::fs::create_dir(dir.path().join("templates")).unwrap();
+        std::fs::write(
+            dir.path().join("templates/main.c"),
+            "int main() { {{INPUT}} }",
+        )
+        .unwrap();
+        std::fs::write(
+            &path,
+            r#"
+[langs.c]
+command = ["gcc", "{{IN}}", "-o", "{{OUT}}"]
+template_file = "templates/main.c"
+
+[langs.c-inline]
+extends = "c"
+template = "{{INPUT}}"
+"#,
+        )
+        .unwrap();
+
+        let config = Config::get_config_from_path(path.clone()).unwrap();
+
+        let c = config.get_lang_conf("c").unwrap();
+        assert_eq!(c.get_raw_template().unwrap(), "int main() { {{INPUT}} }");
+        assert_eq!(
+            c.get_template_file(),
+            Some(dir.path().join("templates/main.c"))
+        );
+
+        // A template replaces the template file of the parent
+        let inline = config.get_lang_conf("c-inline").unwrap();
+        assert_eq!(inline.get_raw_template().unwrap(), "{{INPUT}}");
+        assert_eq!(inline.get_template_file(), None);
+
+        std::fs::remove_file(dir.path().join("templates/main.c")).unwrap();
+
+        match Config::get_config_from_path(path) {
+            Err(QuickerError::InvalidConfig(problems)) => {
+                assert_eq!(problems.len(), 1);
+                assert_eq!(problems[0].get_location(), Some((4, 17)));
+                assert!(problems[0]
+                    .get_message()
+                    .starts_with("`template_file` of `langs.c` is"));
+            }
+            _ => panic!("The missing template file is not reported"),
+        }
+    }
+
     #[test]
     fn it_gets_an_existing_language() {
         let config = get_exmaple_config();
@@ -432,6 +1963,32 @@ int main() {
         assert_eq!(c_conf.get_comment_string().unwrap(), "// %s");
     }
 
+    #[test]
+    pub fn it_does_not_panic_on_an_empty_comment() {
+        let mut config = get_exmaple_config();
+
+        let c_conf = config.get_mut_lang_conf("c").unwrap();
+
+        c_conf.set_raw_comment_string("".to_string());
+
+        assert_eq!(c_conf.get_comment_string().unwrap(), "%s");
+    }
+
+    #[test]
+    pub fn it_hides_lines_with_the_hidden_prefix() {
+        let config = get_exmaple_config();
+        let rust_conf = config.get_lang_conf("rust").unwrap();
+
+        assert_eq!(rust_conf.get_hidden_prefix().unwrap(), "# ");
+        assert_eq!(
+            rust_conf.get_visible_input("# let x = 1;\n#\nprintln!(\"{}\", x);\n#[derive(Debug)]"),
+            "println!(\"{}\", x);\n#[derive(Debug)]"
+        );
+
+        let c_conf = config.get_lang_conf("c").unwrap();
+        assert_eq!(c_conf.get_visible_input("# x"), "# x");
+    }
+
     #[test]
     pub fn it_does_not_add_format_specifier_if_exists() {
         let mut config = get_exmaple_config();
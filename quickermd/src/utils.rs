@@ -1,6 +1,26 @@
-pub fn u8_to_str(u8: &Vec<u8>) -> String {
-    match String::from_utf8(u8.clone()) {
+use std::path::{Component, Path};
+
+pub fn u8_to_str(u8: &[u8]) -> String {
+    match String::from_utf8(u8.to_vec()) {
         Ok(str) => str,
-        Err(_) => String::from_utf8_lossy(&u8).to_string(),
+        Err(_) => String::from_utf8_lossy(u8).to_string(),
     }
 }
+
+/// Whether the relative `path` names a file inside the directory it is
+/// relative to once `.` and `..` are resolved, and not the directory itself
+/// or anything outside of it
+pub fn is_inside_dir(path: &str) -> bool {
+    let mut depth = 0usize;
+
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+
+    depth > 0
+}
@@ -0,0 +1,101 @@
+//! Problems found in a config, reported with the file, line and column
+//! of the setting they are about
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A problem with a setting of a config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigProblem {
+    /// The file the setting came from
+    path: PathBuf,
+
+    /// The line and column of the setting, starting at 1
+    location: Option<(usize, usize)>,
+
+    message: String,
+}
+
+impl ConfigProblem {
+    /// Creates a problem with the setting at `key` of `path`,
+    /// i.e. `["langs", "c", "command"]`
+    pub fn new(path: &Path, key: &[&str], message: String) -> Self {
+        let location = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| locate(&contents, key));
+
+        Self {
+            path: path.to_path_buf(),
+            location,
+            message,
+        }
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the line and column of the setting, starting at 1
+    pub fn get_location(&self) -> Option<(usize, usize)> {
+        self.location
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.to_str().unwrap_or_default();
+
+        match self.location {
+            Some((line, column)) => write!(f, "{}:{}:{}: {}", path, line, column, self.message),
+            None => write!(f, "{}: {}", path, self.message),
+        }
+    }
+}
+
+/// Finds the line and column of the value at `key` in a TOML document,
+/// or of the closest value holding it
+///
+/// ```
+/// use quickermd::validation::locate;
+///
+/// let contents = "[langs.c]\ncomment = \"// \"\ncommand = []\n";
+///
+/// assert_eq!(locate(contents, &["langs", "c", "command"]), Some((3, 11)));
+/// assert_eq!(locate(contents, &["langs", "c", "run"]), Some((1, 1)));
+/// ```
+pub fn locate(contents: &str, key: &[&str]) -> Option<(usize, usize)> {
+    let document = toml_edit::ImDocument::parse(contents).ok()?;
+    let mut item = document.as_item();
+    let mut span = None;
+
+    for segment in key {
+        // Array items are looked up by their index, i.e. `["order", "1"]`
+        let next = match segment.parse::<usize>() {
+            Ok(idx) if item.is_array() => item.get(idx),
+            _ => item.get(*segment),
+        };
+
+        let Some(next) = next else {
+            break;
+        };
+
+        item = next;
+        span = next.span().or(span);
+    }
+
+    let before = &contents[..span?.start];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+
+    Some((line, column))
+}
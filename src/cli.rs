@@ -1,12 +1,18 @@
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use quickermd::output::OutputType;
 use std::io::{self, BufRead, IsTerminal};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
     pub actions: QuickerActions,
+
+    /// Use this config file in place of the global and project configs,
+    /// also set by `QUICKERMD_CONFIG`
+    #[arg(short = 'C', long, global = true)]
+    pub config: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -16,6 +22,54 @@ pub enum QuickerActions {
 
     /// Runs the template + input
     Run(RunArgs),
+
+    /// Runs every code block of a markdown file
+    Md(MdArgs),
+
+    /// Writes the code blocks tagged with `tangle=path` to their files
+    Tangle(TangleArgs),
+
+    /// Shows which config file each setting came from
+    ShowConfig(ShowConfigArgs),
+
+    /// Reports every problem in the config and `output.toml`
+    CheckConfig,
+
+    /// Writes a config for the toolchains found on `PATH`
+    Init(InitArgs),
+
+    /// Prints the JSON Schema of a config file, for TOML editors
+    Schema(SchemaArgs),
+}
+
+#[derive(Args)]
+pub struct SchemaArgs {
+    /// The config file to print the schema of
+    #[arg(value_enum, default_value_t = SchemaFile::Config)]
+    pub file: SchemaFile,
+}
+
+#[derive(Clone, ValueEnum, Debug, Default)]
+pub enum SchemaFile {
+    /// `config.toml`, and every `.quickermd.toml`
+    #[default]
+    Config,
+
+    /// `output.toml`
+    Output,
+}
+
+#[derive(Args)]
+pub struct InitArgs {
+    /// Overwrite the config if it already exists
+    #[arg(short, long, default_value_t = false)]
+    pub force: bool,
+}
+
+#[derive(Args)]
+pub struct ShowConfigArgs {
+    /// Only show the settings of this language
+    pub lang: Option<String>,
 }
 
 #[derive(Args)]
@@ -23,6 +77,10 @@ pub struct DumpArgs {
     /// The language to dump its template
     pub lang: String,
 
+    /// Dump the template of the built-in preset named `lang` instead
+    #[arg(short, long, default_value_t = false)]
+    pub preset: bool,
+
     /// Whether to remove the template lines
     // TODO: Refactor template to add the ability to trim those lines
     #[arg(short, long, default_value_t = false)]
@@ -34,6 +92,7 @@ pub struct DumpArgs {
 
 #[derive(Args)]
 pub struct RunArgs {
+    /// The language, by name, alias, extension or file name
     pub lang: String,
     pub input: Option<String>,
 
@@ -45,26 +104,65 @@ pub struct RunArgs {
     #[arg(short, long, default_value_t = false)]
     pub raw: bool,
 
+    /// Copy the images written to `{{ARTIFACTS}}` to this directory
+    #[arg(short, long)]
+    pub artifacts: Option<PathBuf>,
+
     /// Format style
     #[arg(value_enum, short, long, default_value_t = OutputFormat::Pretty)]
     pub format: OutputFormat,
 }
 
-#[derive(Clone, ValueEnum, Debug)]
+#[derive(Args)]
+pub struct MdArgs {
+    /// The markdown, org, reStructuredText or AsciiDoc file to run
+    pub file: PathBuf,
+
+    /// Show the input that was used to run
+    #[arg(short, long, default_value_t = false)]
+    pub show_input: bool,
+
+    /// Write the output of each block below it, in the file
+    #[arg(short, long, default_value_t = false)]
+    pub write: bool,
+
+    /// Only run the block containing this line
+    #[arg(short, long)]
+    pub line: Option<usize>,
+
+    /// Every block of a language builds on the blocks before it
+    #[arg(long, default_value_t = false)]
+    pub sessions: bool,
+
+    /// Check each block against the `expected` or `output` block below it
+    #[arg(short, long, default_value_t = false, conflicts_with = "write")]
+    pub check: bool,
+
+    /// Format style
+    #[arg(value_enum, short, long, default_value_t = OutputFormat::Pretty)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args)]
+pub struct TangleArgs {
+    /// The markdown file to tangle
+    pub file: PathBuf,
+
+    /// Wrap each file in the template of its language
+    #[arg(short, long, default_value_t = false)]
+    pub template: bool,
+}
+
+#[derive(Clone, ValueEnum, Debug, Default)]
 pub enum OutputFormat {
     Json,
     JsonPretty,
+    #[default]
     Pretty,
     Comment,
     Raw,
 }
 
-impl Default for OutputFormat {
-    fn default() -> Self {
-        OutputFormat::Pretty
-    }
-}
-
 impl From<String> for OutputFormat {
     fn from(value: String) -> Self {
         match value.to_lowercase().as_str() {
@@ -90,9 +188,9 @@ impl std::fmt::Display for OutputFormat {
     }
 }
 
-impl Into<OutputType> for OutputFormat {
-    fn into(self) -> OutputType {
-        match self {
+impl From<OutputFormat> for OutputType {
+    fn from(value: OutputFormat) -> Self {
+        match value {
             OutputFormat::Json => OutputType::JSON,
             OutputFormat::JsonPretty => OutputType::JsonPretty,
             _ => OutputType::Raw,
@@ -1,31 +1,56 @@
 use clap::Parser;
+use quickermd::document::{BlockResult, BlockStatus, CheckStatus, Document, ResultBlock};
 use quickermd::output::Output;
-use quickermd::QuickerMD;
+use quickermd::user_config::{Config, LanguageConfig, Template};
+use quickermd::{presets, toolchains, QuickerMD, RunOptions};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
 
 mod cli;
 mod outputer;
 mod resolver;
 mod utils;
 use crate::cli::OutputFormat;
+use crate::outputer::OutputArgs;
+use crate::outputer::SectionType;
 
 fn dump_template(quicker: &QuickerMD, args: &cli::DumpArgs) {
-    let template;
-    let mut has_input = false;
+    let input = resolver::input(&args.input);
+    let has_input = input.is_some();
+    let input = input.unwrap_or_default();
 
-    if let Some(input) = resolver::input(&args.input) {
-        has_input = true;
-        template = quicker.get_template(&args.lang, input);
+    let template = if args.preset {
+        let Some(lang_conf) = LanguageConfig::from_preset(&args.lang) else {
+            utils::exit(
+                &format!(
+                    "No preset named `{}`, expected one of {}",
+                    args.lang,
+                    presets::names().join(", ")
+                ),
+                1,
+            );
+        };
+
+        lang_conf
+            .get_raw_template()
+            .map(|_| Template::new(&args.lang, lang_conf.get_runnable_lines(input), &lang_conf))
     } else {
-        template = quicker.get_template(&args.lang, Vec::with_capacity(0));
-    }
+        // The template is printed on its own, so that it can be redirected to a file
+        if let Ok(lang_conf) = quicker.get_config_for_lang(&args.lang) {
+            if let Some(file) = lang_conf.get_template_file() {
+                eprintln!("Template from `{}`", file.to_str().unwrap_or_default());
+            }
+        }
+
+        quicker.get_template(&args.lang, input)
+    };
 
     if let Some(tmpl) = template {
-        let output;
-        if has_input {
-            output = tmpl.to_string();
+        let output = if has_input {
+            tmpl.to_string()
         } else {
-            output = tmpl.get_template_lines().join("\n");
-        }
+            tmpl.get_template_lines().join("\n")
+        };
 
         println!("{}", output);
     } else {
@@ -33,18 +58,30 @@ fn dump_template(quicker: &QuickerMD, args: &cli::DumpArgs) {
     }
 }
 
-fn output_pretty(input: String, output: &Output, show_input: bool) -> std::io::Result<()> {
-    let output_config = resolver::output(input, output, show_input);
+fn output_pretty(
+    output_args: &OutputArgs,
+    input: String,
+    output: &Output,
+    show_input: bool,
+) -> std::io::Result<()> {
+    let output_config = resolver::output(output_args, input, output, show_input);
     output_config.write_pretty_to_console()?;
 
     Ok(())
 }
 
-fn output_as_comment(quicker: &mut QuickerMD, input: String, output: &Output, args: &cli::RunArgs) {
-    let output_config = resolver::output(input, output, args.show_input);
+fn output_as_comment(
+    quicker: &mut QuickerMD,
+    output_args: &OutputArgs,
+    lang: &str,
+    input: String,
+    output: &Output,
+    show_input: bool,
+) {
+    let output_config = resolver::output(output_args, input, output, show_input);
 
     let comment = quicker
-        .get_config_for_lang(&args.lang)
+        .get_config_for_lang(lang)
         .unwrap()
         .get_comment_string()
         .unwrap_or("%s".to_string());
@@ -52,47 +89,502 @@ fn output_as_comment(quicker: &mut QuickerMD, input: String, output: &Output, ar
     output_config.write_as_comment(&comment);
 }
 
-fn output_raw(input: String, output: &Output, args: &cli::RunArgs) {
-    let output_config = resolver::output(input, output, args.show_input);
-    output_config.write_as_comment("");
+fn output_raw(output_args: &OutputArgs, input: String, output: &Output, show_input: bool) {
+    let output_config = resolver::output(output_args, input, output, show_input);
+    output_config.write_as_comment("%s");
+}
+
+/// Removes the hidden lines of `lang` from `input`
+fn visible_input(quicker: &QuickerMD, lang: &str, input: String) -> String {
+    match quicker.get_config_for_lang(lang) {
+        Ok(lang_conf) => lang_conf.get_visible_input(&input),
+        Err(_) => input,
+    }
+}
+
+fn output_with_format(
+    quicker: &mut QuickerMD,
+    output_args: &OutputArgs,
+    lang: &str,
+    input: String,
+    mut output: Output,
+    format: &OutputFormat,
+    show_input: bool,
+) {
+    let input = visible_input(quicker, lang, input);
+
+    match format {
+        OutputFormat::Json | OutputFormat::JsonPretty => {
+            output.output_as(format.clone().into());
+            println!("{}", output);
+        }
+        OutputFormat::Raw => output_raw(output_args, input, &output, show_input),
+        OutputFormat::Pretty => output_pretty(output_args, input, &output, show_input).unwrap(),
+        OutputFormat::Comment => {
+            output_as_comment(quicker, output_args, lang, input, &output, show_input)
+        }
+    }
 }
 
-fn run_input(quicker: &mut QuickerMD, args: &cli::RunArgs) {
+fn run_input(quicker: &mut QuickerMD, output_args: &OutputArgs, args: &cli::RunArgs) {
     let Some(input_vec) = resolver::input(&args.input) else {
         utils::exit("No Input Found", 1);
     };
 
-    let result = quicker
-        .run(&args.lang, input_vec.join("\n").to_string())
-        .map_err(|e| format!("Error running `{}`:\n{}", args.lang, e.to_string()));
+    let mut options = RunOptions::default();
+    options.set_artifacts_dir(args.artifacts.clone());
+
+    let result = quicker.run_with_options(&args.lang, input_vec.join("\n").to_string(), &options);
+
+    match result {
+        Ok(output) => {
+            // JSON output already lists the artifacts
+            if !matches!(args.format, OutputFormat::Json | OutputFormat::JsonPretty) {
+                print_artifacts(&output);
+            }
+            output_with_format(
+                quicker,
+                output_args,
+                &args.lang,
+                input_vec.join("\n"),
+                output,
+                &args.format,
+                args.show_input,
+            )
+        }
+        Err(e) => utils::exit(
+            &format!("Error running `{}`:\n{}", args.lang, e),
+            utils::exit_code(&e),
+        ),
+    }
+}
+
+fn write_markdown(
+    quicker: &QuickerMD,
+    output_args: &OutputArgs,
+    document: &Document,
+    results: &[BlockResult],
+    args: &cli::MdArgs,
+) {
+    let mut result_blocks: Vec<Option<Vec<ResultBlock>>> =
+        document.get_blocks().iter().map(|_| None).collect();
+
+    for result in results {
+        let BlockStatus::Ran(output) = result.get_status() else {
+            continue;
+        };
+
+        let block = document.get_block_for(result);
+        let input = visible_input(quicker, block.get_lang(), block.get_input());
+        let output_config = resolver::output(output_args, input, output, args.show_input);
+
+        let sections = output_config
+            .get_sections()
+            .into_iter()
+            .filter(|(_, section)| !section.is_empty())
+            .map(|(kind, section)| {
+                ResultBlock::new(kind, Some(section.get_name()), &section.get_value())
+            });
+
+        // Images are linked relative to the document
+        let root = args.file.parent().unwrap_or(std::path::Path::new(""));
+        let artifacts = output.get_artifacts().iter().map(|artifact| {
+            let relative = artifact.strip_prefix(root).unwrap_or(artifact);
+            ResultBlock::artifact(&relative.to_string_lossy().replace('\\', "/"))
+        });
+
+        result_blocks[block.get_index()] = Some(sections.chain(artifacts).collect());
+    }
+
+    if let Err(e) = std::fs::write(&args.file, document.with_results(&result_blocks)) {
+        utils::exit(
+            &format!(
+                "Error writing file `{}`:\n{}",
+                args.file.to_str().unwrap_or_default(),
+                e
+            ),
+            1,
+        );
+    }
+
+    // The images of blocks that were changed or removed are no longer linked
+    if let Err(e) = document.remove_unused_artifacts() {
+        eprintln!("Error removing unused artifacts:\n{}", e);
+    }
+}
+
+fn check_markdown(document: &Document, results: &[BlockResult], args: &cli::MdArgs) {
+    let file = args.file.to_str().unwrap_or_default();
+    let (mut passed, mut failed) = (0, 0);
+
+    for result in results {
+        let header = format!(
+            "{}:{}-{} ({})",
+            file,
+            result.get_start(),
+            result.get_end(),
+            result.get_lang()
+        );
 
-    if let Ok(mut output) = result {
-        match &args.format {
-            OutputFormat::Json | &OutputFormat::JsonPretty => {
-                output.output_as(args.format.clone().into());
-                println!("{}", output.to_string());
+        match result.check(document.get_block_for(result)) {
+            CheckStatus::Skipped(_) => continue,
+            CheckStatus::Passed => {
+                passed += 1;
+                println!("{} ... ok", header);
             }
-            OutputFormat::Raw => output_raw(input_vec.join("\n"), &output, args),
-            OutputFormat::Pretty => {
-                output_pretty(input_vec.join("\n"), &output, args.show_input).unwrap()
+            CheckStatus::Failed(e) => {
+                failed += 1;
+                println!("{} ... FAILED\n{}", header, e);
             }
-            OutputFormat::Comment => {
-                output_as_comment(quicker, input_vec.join("\n"), &output, args)
+            CheckStatus::Mismatch { expected, actual } => {
+                failed += 1;
+                println!("{} ... FAILED", header);
+                print!(
+                    "{}",
+                    similar::TextDiff::from_lines(&expected, &actual)
+                        .unified_diff()
+                        .header("expected", "actual")
+                );
+            }
+        }
+    }
+
+    println!("\ncheck result: {} passed; {} failed", passed, failed);
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn run_markdown(quicker: &mut QuickerMD, output_args: &OutputArgs, args: &cli::MdArgs) {
+    let document = Document::from_path(&args.file).unwrap_or_else(|e| utils::exit_with_error(&e));
+    let results = match args.line {
+        Some(line) => match quicker.run_at_line(&document, line, args.sessions) {
+            Ok(result) => vec![result],
+            Err(e) => utils::exit_with_error(&e),
+        },
+        None => quicker.run_document(&document, args.sessions),
+    };
+
+    let has_failure = results
+        .iter()
+        .any(|result| matches!(result.get_status(), BlockStatus::Failed(_)));
+
+    if args.check {
+        check_markdown(&document, &results, args);
+    } else if args.write {
+        write_markdown(quicker, output_args, &document, &results, args);
+
+        for result in results.iter() {
+            if let BlockStatus::Failed(e) = result.get_status() {
+                eprintln!(
+                    "{}:{}: {}",
+                    args.file.to_str().unwrap_or_default(),
+                    result.get_start(),
+                    e
+                );
             }
         }
     } else {
-        println!("{}", result.err().unwrap());
+        print_markdown_results(quicker, output_args, &document, results, args);
+    }
+
+    if has_failure {
+        std::process::exit(1);
+    }
+}
+
+fn print_markdown_results(
+    quicker: &mut QuickerMD,
+    output_args: &OutputArgs,
+    document: &Document,
+    results: Vec<BlockResult>,
+    args: &cli::MdArgs,
+) {
+    match args.format {
+        // A single block is printed on its own, for editors running the block at the cursor
+        OutputFormat::Json if args.line.is_some() => {
+            println!("{}", serde_json::to_string(&results[0]).unwrap())
+        }
+        OutputFormat::JsonPretty if args.line.is_some() => {
+            println!("{}", serde_json::to_string_pretty(&results[0]).unwrap())
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string(&results).unwrap()),
+        OutputFormat::JsonPretty => {
+            println!("{}", serde_json::to_string_pretty(&results).unwrap())
+        }
+        _ => {
+            let file = args.file.to_str().unwrap_or_default();
+
+            for result in results {
+                let block = document.get_block_for(&result);
+
+                println!(
+                    "{}:{}-{} ({})",
+                    file,
+                    result.get_start(),
+                    result.get_end(),
+                    result.get_lang()
+                );
+
+                match result.get_status() {
+                    BlockStatus::Ran(output) => {
+                        print_artifacts(output);
+                        output_with_format(
+                            quicker,
+                            output_args,
+                            result.get_lang(),
+                            block.get_input(),
+                            output.clone(),
+                            &args.format,
+                            args.show_input,
+                        )
+                    }
+                    BlockStatus::Skipped(reason) => println!("Skipped: {}", reason),
+                    BlockStatus::Failed(e) => println!("{}", e),
+                }
+            }
+        }
+    }
+}
+
+/// Lists the copied images on stderr, so they never end up in output that is
+/// inserted into a file, like `--format comment`
+fn print_artifacts(output: &Output) {
+    for artifact in output.get_artifacts() {
+        eprintln!("Wrote `{}`", artifact.to_str().unwrap_or_default());
     }
 }
 
+fn tangle(quicker: &QuickerMD, args: &cli::TangleArgs) {
+    let document = Document::from_path(&args.file).unwrap_or_else(|e| utils::exit_with_error(&e));
+    let root = args.file.parent().unwrap_or(std::path::Path::new(""));
+    let files = document.tangle();
+
+    // Nothing is written when a block would write outside of the document's directory
+    if let Some(file) = files.iter().find(|file| !file.is_inside_document_dir()) {
+        utils::exit(
+            &format!(
+                "`tangle={}` must be a relative path inside the directory of `{}`",
+                file.get_path(),
+                args.file.to_str().unwrap_or_default()
+            ),
+            1,
+        );
+    }
+
+    for file in files {
+        let lang = file.get_lang();
+        let has_template = quicker.get_template_for_lang(lang).is_some();
+
+        let mut contents = if args.template && has_template {
+            quicker
+                .get_template(lang, file.get_lines().clone())
+                .unwrap()
+                .to_string()
+        } else {
+            match quicker.get_config_for_lang(lang) {
+                Ok(lang_conf) => lang_conf
+                    .get_runnable_lines(file.get_lines().clone())
+                    .join("\n"),
+                Err(_) => file.get_contents(),
+            }
+        };
+
+        if !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+
+        let path = root.join(file.get_path());
+
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, contents));
+
+        match written {
+            Ok(_) => println!("Wrote `{}`", path.to_str().unwrap_or_default()),
+            Err(e) => utils::exit(
+                &format!(
+                    "Error writing file `{}`:\n{}",
+                    path.to_str().unwrap_or_default(),
+                    e
+                ),
+                1,
+            ),
+        }
+    }
+}
+
+fn show_config(quicker: &QuickerMD, args: &cli::ShowConfigArgs) {
+    // Aliases show the settings of their language
+    let prefix = args.lang.as_ref().map(|lang| {
+        let lang = quicker.resolve_lang(lang).unwrap_or_else(|_| lang.clone());
+        format!("langs.{}.", lang)
+    });
+    let sources: Vec<(&String, &std::path::PathBuf)> = quicker
+        .get_config()
+        .get_sources()
+        .iter()
+        .filter(|(key, _)| prefix.as_ref().is_none_or(|prefix| key.starts_with(prefix)))
+        .collect();
+
+    if sources.is_empty() {
+        if let Some(lang) = &args.lang {
+            utils::exit(&format!("No config for `{}`", lang), 1);
+        }
+    }
+
+    let width = sources.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+
+    for (key, path) in sources {
+        println!("{:width$}  {}", key, path.to_str().unwrap_or_default());
+    }
+}
+
+/// Validates the config without loading it, so that every problem is reported
+fn check_config(path: Option<&PathBuf>) {
+    let config = match path {
+        Some(path) => Config::from_layers_unchecked(std::slice::from_ref(path)),
+        None => Config::load_unchecked(),
+    }
+    .unwrap_or_else(|e| utils::exit_with_error(&e));
+
+    let mut problems = config.validate();
+    // The built-in config and presets are not files
+    let mut files: BTreeSet<PathBuf> = config
+        .get_sources()
+        .values()
+        .filter(|path| path.is_file())
+        .cloned()
+        .collect();
+
+    let output_path = OutputArgs::get_path().unwrap_or_else(|e| utils::exit_with_error(&e));
+    let output_config = OutputArgs::load_unchecked(
+        output_path.clone(),
+        String::new(),
+        String::new(),
+        String::new(),
+    )
+    .unwrap_or_else(|e| utils::exit_with_error(&e));
+
+    problems.extend(output_config.validate(&output_path));
+    if output_path.exists() {
+        files.insert(output_path);
+    }
+
+    for file in files.iter() {
+        println!("Checked `{}`", file.to_str().unwrap_or_default());
+    }
+
+    if !files.is_empty() {
+        println!();
+    }
+
+    if problems.is_empty() {
+        println!("No problems found");
+        return;
+    }
+
+    for problem in problems.iter() {
+        println!("{}", problem);
+    }
+
+    utils::exit(&format!("\n{} problem(s) found", problems.len()), 1);
+}
+
+/// Writes a config for the toolchains on `PATH`, to `path`, `QUICKERMD_CONFIG`
+/// or the global config
+fn init(path: Option<&PathBuf>, args: &cli::InitArgs) {
+    let path = match path {
+        Some(path) => path.clone(),
+        None => Config::get_path().unwrap_or_else(|e| utils::exit_with_error(&e)),
+    };
+    let file = path.to_str().unwrap_or_default();
+
+    if path.exists() && !args.force {
+        utils::exit(
+            &format!("`{}` already exists, use `--force` to overwrite it", file),
+            1,
+        );
+    }
+
+    let found = toolchains::detect();
+    if found.is_empty() {
+        utils::exit("No toolchains found on PATH", 1);
+    }
+
+    for toolchain in found.iter() {
+        println!(
+            "Found `{}` for `{}`",
+            toolchain.get_program(),
+            toolchain.get_lang()
+        );
+    }
+
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, toolchains::generate_config(&found)));
+
+    match written {
+        Ok(_) => println!("Wrote `{}`", file),
+        Err(e) => utils::exit(&format!("Error writing file `{}`:\n{}", file, e), 1),
+    }
+}
+
+/// Prints the JSON Schema of `file`
+fn schema(file: &cli::SchemaFile) {
+    let schema = match file {
+        cli::SchemaFile::Config => Config::schema(),
+        cli::SchemaFile::Output => OutputArgs::schema(),
+    };
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&schema).expect("The schema is valid JSON")
+    );
+}
+
 fn main() {
     let cli = cli::Cli::parse();
 
-    // TODO: Handle error
-    let mut quicker = QuickerMD::new().unwrap();
+    match &cli.actions {
+        cli::QuickerActions::CheckConfig => {
+            check_config(cli.config.as_ref());
+            return;
+        }
+        cli::QuickerActions::Init(args) => {
+            init(cli.config.as_ref(), args);
+            return;
+        }
+        cli::QuickerActions::Schema(args) => {
+            schema(&args.file);
+            return;
+        }
+        _ => {}
+    }
+
+    let quicker = match &cli.config {
+        Some(path) => QuickerMD::from_file_path(path.clone()),
+        None => QuickerMD::new(),
+    };
+    let mut quicker = quicker.unwrap_or_else(|e| utils::exit_with_error(&e));
 
     match cli.actions {
         cli::QuickerActions::DumpTemplate(args) => dump_template(&quicker, &args),
-        cli::QuickerActions::Run(args) => run_input(&mut quicker, &args),
+        cli::QuickerActions::Run(args) => {
+            run_input(&mut quicker, &resolver::output_config(), &args)
+        }
+        cli::QuickerActions::Md(args) => {
+            run_markdown(&mut quicker, &resolver::output_config(), &args)
+        }
+        cli::QuickerActions::Tangle(args) => tangle(&quicker, &args),
+        cli::QuickerActions::ShowConfig(args) => show_config(&quicker, &args),
+        cli::QuickerActions::CheckConfig
+        | cli::QuickerActions::Init(_)
+        | cli::QuickerActions::Schema(_) => {
+            unreachable!("Handled before loading the config")
+        }
     }
 }
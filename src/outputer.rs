@@ -1,16 +1,24 @@
-use crate::cli::OutputFormat;
 use directories::ProjectDirs;
-use std::io::{Error, ErrorKind, IsTerminal};
+use quickermd::schema;
+use quickermd::validation::ConfigProblem;
+use quickermd::QuickerError;
+use std::io::{ErrorKind, IsTerminal};
 use std::str::FromStr;
-use std::{collections::HashMap, io::Write, path::PathBuf};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
 use termcolor::{Buffer, BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
 
 use serde::Deserialize;
+use serde_json::json;
 
-#[derive(Deserialize, Debug)]
-pub struct OutputArgs<'output> {
+#[derive(Deserialize, Debug, Clone)]
+pub struct OutputArgs {
     order: Vec<String>,
 
+    // Only `--show-input` shows the input for now
+    #[allow(dead_code)]
     show_input: bool,
 
     #[serde(default = "default_input")]
@@ -21,12 +29,6 @@ pub struct OutputArgs<'output> {
 
     #[serde(default = "default_error")]
     error: Section,
-
-    #[serde(skip)]
-    raw_input: &'output str,
-
-    #[serde(skip)]
-    style: OutputFormat,
 }
 
 pub fn default_input() -> Section {
@@ -68,7 +70,7 @@ pub fn default_error() -> Section {
     }
 }
 
-impl<'output> Default for OutputArgs<'output> {
+impl Default for OutputArgs {
     fn default() -> Self {
         Self {
             order: vec![
@@ -80,8 +82,6 @@ impl<'output> Default for OutputArgs<'output> {
             input: default_input(),
             output: default_output(),
             error: default_error(),
-            raw_input: "",
-            style: OutputFormat::default(),
         }
     }
 }
@@ -103,14 +103,6 @@ pub trait SectionType {
         format!("{} {}", self.get_icon(), self.get_name())
     }
 
-    fn write(&self) {
-        if self.is_empty() {
-            return;
-        }
-
-        println!("{}", self.get_value());
-    }
-
     fn write_as_comment(&self, comment_string: &str) {
         if self.is_empty() {
             return;
@@ -131,7 +123,7 @@ pub trait SectionType {
 
         let value = self.get_value();
 
-        let color = Color::from_str(&self.get_color()).unwrap_or_else(|_| Color::Blue);
+        let color = Color::from_str(&self.get_color()).unwrap_or(Color::Blue);
         let mut color_spec = ColorSpec::new();
 
         if self.wants_bold() {
@@ -163,7 +155,7 @@ pub trait SectionType {
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Section {
     name: String,
 
@@ -189,6 +181,31 @@ pub struct Section {
     value: String,
 }
 
+impl Section {
+    /// Returns the JSON Schema of a section
+    fn schema(description: &str) -> serde_json::Value {
+        let mut section = schema::table::<Section>(|setting| {
+            Some(match setting {
+                "name" => schema::string("The name shown in the header of the section"),
+                "icon" => schema::string("The icon shown before the name"),
+                "color" => schema::string(
+                    "The color of the header, a name like `blue`, an ANSI 256 color like `33`, or `r,g,b`",
+                ),
+                "bold" => schema::boolean("Whether the header is bold"),
+                "italic" => schema::boolean("Whether the header is italic"),
+                "strikethrough" => schema::boolean("Whether the header is struck through"),
+                "underline" => schema::boolean("Whether the header is underlined"),
+                _ => return None,
+            })
+        });
+
+        section["required"] = json!(["name"]);
+        section["description"] = json!(description);
+
+        section
+    }
+}
+
 impl SectionType for Section {
     fn get_value(&self) -> String {
         self.value.clone()
@@ -235,7 +252,7 @@ pub fn default_icon() -> String {
     "#".to_string()
 }
 
-impl<'output> OutputArgs<'output> {
+impl OutputArgs {
     pub fn write_as_comment(&self, comment_string: &str) {
         for item in self.order.iter() {
             let section = match item.as_str() {
@@ -249,7 +266,7 @@ impl<'output> OutputArgs<'output> {
     }
     pub fn write_pretty_to_console(&self) -> std::io::Result<()> {
         if !std::io::stdin().is_terminal() {
-            self.write_as_comment("");
+            self.write_as_comment("%s");
             return Ok(());
         }
 
@@ -264,13 +281,29 @@ impl<'output> OutputArgs<'output> {
                 _ => unreachable!("Should of been checked when resolving config"),
             };
 
-            section.write_pretty(&mut buffer);
+            section.write_pretty(&mut buffer)?;
         }
 
         bufwtr.print(&buffer)?;
         Ok(())
     }
 
+    /// Returns the sections in the configured `order`, along with their key
+    pub fn get_sections(&self) -> Vec<(&str, &Section)> {
+        self.order
+            .iter()
+            .map(|item| {
+                let section = match item.as_str() {
+                    "input" => &self.input,
+                    "output" => &self.output,
+                    "error" => &self.error,
+                    _ => unreachable!("Should of been checked when resolving config"),
+                };
+                (item.as_str(), section)
+            })
+            .collect()
+    }
+
     pub fn get_input(&mut self) -> &mut Section {
         &mut self.input
     }
@@ -280,7 +313,25 @@ impl<'output> OutputArgs<'output> {
         input: String,
         output: String,
         error: String,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, QuickerError> {
+        let config = OutputArgs::load_unchecked(path.clone(), input, output, error)?;
+        let problems = config.validate(&path);
+
+        if !problems.is_empty() {
+            return Err(QuickerError::InvalidConfig(problems));
+        }
+
+        Ok(config)
+    }
+
+    /// Reads the config the same way as `OutputArgs::get_config_from_path`,
+    /// without validating it
+    pub fn load_unchecked(
+        path: PathBuf,
+        input: String,
+        output: String,
+        error: String,
+    ) -> Result<Self, QuickerError> {
         let read_result = std::fs::read_to_string(path.clone());
         let config_contents;
 
@@ -290,22 +341,17 @@ impl<'output> OutputArgs<'output> {
                 output_arg.set_reserved_section_values(input, output, error);
                 return Ok(output_arg);
             } else {
-                return Err(format!(
-                    "Error reading file `{}`:\n{}",
-                    path.to_str().unwrap_or_default(),
-                    e.to_string()
-                ));
+                return Err(QuickerError::ReadFile { path, source: e });
             }
         } else {
             config_contents = read_result.unwrap();
         }
 
-        let mut config = toml::from_str::<OutputArgs>(&config_contents).map_err(|e| {
-            format!(
-                "There was an error reading the config '{}':\n{}",
-                path.to_str().unwrap_or_default(),
-                e.to_string()
-            )
+        let mut config = toml::from_str::<OutputArgs>(&config_contents).map_err(|source| {
+            QuickerError::ConfigParse {
+                paths: vec![path.clone()],
+                source,
+            }
         })?;
 
         config.set_reserved_section_values(input, output, error);
@@ -313,13 +359,58 @@ impl<'output> OutputArgs<'output> {
         Ok(config)
     }
 
-    pub fn get_config(input: String, output: String, error: String) -> Result<Self, String> {
-        let path = ProjectDirs::from("", "", "QuickMD")
-            .ok_or("Could not resolve project directory")?
+    pub fn get_config(input: String, output: String, error: String) -> Result<Self, QuickerError> {
+        OutputArgs::get_config_from_path(OutputArgs::get_path()?, input, output, error)
+    }
+
+    pub fn get_path() -> Result<PathBuf, QuickerError> {
+        Ok(ProjectDirs::from("", "", "QuickMD")
+            .ok_or(QuickerError::NoConfigDir)?
             .config_local_dir()
-            .join("output.toml");
+            .join("output.toml"))
+    }
+
+    /// Returns the JSON Schema of `output.toml`
+    pub fn schema() -> serde_json::Value {
+        let output = schema::table::<OutputArgs>(|setting| {
+            Some(match setting {
+                "order" => json!({
+                    "type": "array",
+                    "items": { "enum": ["input", "output", "error"] },
+                    "description": "The order the sections are written in",
+                }),
+                "show_input" => schema::boolean("Whether the input section is written"),
+                "input" => Section::schema("The section of the input"),
+                "output" => Section::schema("The section of the output"),
+                "error" => Section::schema("The section of the errors"),
+                _ => return None,
+            })
+        });
+
+        let mut output = schema::document("QuickerMD output", output);
+        output["required"] = json!(["order", "show_input"]);
+
+        output
+    }
 
-        OutputArgs::get_config_from_path(path, input, output, error)
+    /// Finds the section names in `order` that are not a section, which
+    /// would otherwise be found when writing the output
+    pub fn validate(&self, path: &Path) -> Vec<ConfigProblem> {
+        self.order
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| !matches!(item.as_str(), "input" | "output" | "error"))
+            .map(|(idx, item)| {
+                ConfigProblem::new(
+                    path,
+                    &["order", &idx.to_string()],
+                    format!(
+                        "`{}` in `order` is not a section, expected `input`, `output` or `error`",
+                        item
+                    ),
+                )
+            })
+            .collect()
     }
 
     pub fn set_reserved_section_values(&mut self, input: String, output: String, error: String) {
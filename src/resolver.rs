@@ -1,4 +1,4 @@
-use crate::{cli, outputer::{OutputArgs, SectionType}};
+use crate::{cli, outputer::{OutputArgs, SectionType}, utils};
 use quickermd::output::Output;
 
 pub fn input(input: &Option<String>) -> Option<Vec<String>> {
@@ -16,14 +16,21 @@ pub fn input(input: &Option<String>) -> Option<Vec<String>> {
     Some(input_vec)
 }
 
-pub fn output(input: String, output: &Output, show_input: bool) -> OutputArgs {
-    let mut output_config =
-        OutputArgs::get_config(input.clone(), output.get_stdout(), output.get_stderr())
-            .unwrap_or_else(|_| {
-                let mut conf = OutputArgs::default();
-                conf.set_reserved_section_values(input, output.get_stdout(), output.get_stderr());
-                conf
-            });
+/// Loads `output.toml` before anything runs, so that a bad config does not
+/// throw away the output of a run
+pub fn output_config() -> OutputArgs {
+    OutputArgs::get_config(String::new(), String::new(), String::new())
+        .unwrap_or_else(|e| utils::exit_with_error(&e))
+}
+
+pub fn output(
+    output_args: &OutputArgs,
+    input: String,
+    output: &Output,
+    show_input: bool,
+) -> OutputArgs {
+    let mut output_config = output_args.clone();
+    output_config.set_reserved_section_values(input, output.get_stdout(), output.get_stderr());
 
     if !show_input {
         output_config
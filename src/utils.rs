@@ -1,4 +1,26 @@
+use quickermd::QuickerError;
+
 pub fn exit(message: &str, code: i32) -> ! {
     eprintln!("{}", message);
     std::process::exit(code);
 }
+
+/// The exit code for each kind of error, after the codes used by clap
+pub fn exit_code(error: &QuickerError) -> i32 {
+    match error {
+        QuickerError::UnknownLanguage(_) => 3,
+        QuickerError::NoBlockAtLine(_) => 4,
+        QuickerError::NoConfigDir => 5,
+        QuickerError::ConfigNotFound(_) => 6,
+        QuickerError::ConfigParse { .. } => 7,
+        QuickerError::InvalidConfig(_) => 8,
+        QuickerError::ReadFile { .. } => 9,
+        QuickerError::Spawn { .. } => 10,
+        QuickerError::Timeout(_) => 11,
+        QuickerError::Io(_) => 12,
+    }
+}
+
+pub fn exit_with_error(error: &QuickerError) -> ! {
+    exit(&error.to_string(), exit_code(error));
+}
@@ -19,7 +19,7 @@ where
         Self { variables: hash }
     }
 
-    pub fn parse_string_vec(&self, vec: &mut Vec<String>) {
+    pub fn parse_string_vec(&self, vec: &mut [String]) {
         for item in vec.iter_mut() {
             if let Some(val) = self.variables.get(item.as_str()) {
                 *item = val.0.to_string()
@@ -27,7 +27,7 @@ where
         }
     }
 
-    pub fn parse_with_tracker(&mut self, vec: &mut Vec<String>) {
+    pub fn parse_with_tracker(&mut self, vec: &mut [String]) {
         for item in vec.iter_mut() {
             if let Some(val) = self.variables.get_mut(item.as_str()) {
                 *item = val.0.to_string();